@@ -1,29 +1,30 @@
 use std::time::Instant;
 
 use crate::*;
-use tetris::State;
+use tetris::{Ruleset, State};
 
 pub struct Bot {
     pub evaluator: Evaluator,
+    pub ruleset: Ruleset,
 }
 
 impl Bot {
-    pub fn new(evaluator: Evaluator) -> Self {
-        Self { evaluator }
+    pub fn new(evaluator: Evaluator, ruleset: Ruleset) -> Self {
+        Self { evaluator, ruleset }
     }
 
     pub fn get_move_for_repeat(&self, state: State, repeat: u32) -> Option<State> {
-        let mut root = Node::new(&self.evaluator, state);
+        let mut root = Node::new(&self.evaluator, &self.ruleset, self.visible_state(&state));
 
         for _ in 0..repeat {
             root.search();
         }
 
-        root.best_child().map(|best_child| best_child.state.clone())
+        self.chosen_state(&state, &root)
     }
 
     pub fn get_move_for_time(&self, state: State, time: u32) -> Option<State> {
-        let mut root = Node::new(&self.evaluator, state);
+        let mut root = Node::new(&self.evaluator, &self.ruleset, self.visible_state(&state));
 
         let start = Instant::now();
         let duration = std::time::Duration::from_millis(time as u64);
@@ -32,6 +33,43 @@ impl Bot {
             root.search();
         }
 
-        root.best_child().map(|best_child| best_child.state.clone())
+        self.chosen_state(&state, &root)
     }
+
+    fn chosen_state(&self, state: &State, root: &Node) -> Option<State> {
+        let best_child = root.best_child()?;
+        let mut next_state = restore_next_pieces(state, &root.state, &best_child.state);
+
+        // the search does not keep the inputs, so they are searched again for the chosen move
+        if let (Some(placement), Some(last_action)) =
+            (best_child.placement, next_state.last_action.as_mut())
+        {
+            last_action.movements_history = root
+                .state
+                .finesse(&placement, &self.ruleset)
+                .unwrap_or_default();
+        }
+
+        Some(next_state)
+    }
+
+    // the search only knows the pieces in the preview, as in real clients
+    fn visible_state(&self, state: &State) -> State {
+        let mut visible_state = state.clone();
+        visible_state.truncate_next_pieces(self.ruleset.preview);
+        visible_state
+    }
+}
+
+// gives back the pieces hidden from the search to the chosen state
+fn restore_next_pieces(state: &State, visible_state: &State, next_state: &State) -> State {
+    let consumed = visible_state.next_pieces.len() - next_state.next_pieces.len();
+
+    let mut next_state = next_state.clone();
+    next_state.next_pieces = state.next_pieces.iter().skip(consumed).copied().collect();
+    next_state.bag = state.bag;
+    if next_state.current_piece.is_none() {
+        next_state.current_piece = next_state.next_pieces.pop_front();
+    }
+    next_state
 }
@@ -1,11 +1,14 @@
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use tetris::{combo_attack, row_x, Board, State};
+use tetris::{row_x, Board, Ruleset, State};
 
 pub type Score = i32;
 pub type Reward = Score;
 pub type Value = Score;
 
+// the value of a state where the next piece cannot spawn, worse than any board
+pub(crate) const DEATH: Value = -1_000_000;
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Default)]
 pub struct Evaluator {
     // value
@@ -26,6 +29,10 @@ pub struct Evaluator {
     pub hight: i32,
     pub hight_sq: i32,
     pub b2b: i32,
+    #[serde(default)]
+    pub b2b_chain: i32,
+    #[serde(default)]
+    pub pending_garbage: i32, // lines that will enter the board unless cancelled
     pub holding: [i32; 5], // (S, Z), (J, L), T, O, I  // TODO: put in order
 
     // reward
@@ -41,7 +48,7 @@ pub struct Evaluator {
 }
 
 impl Evaluator {
-    pub fn evaluate(&self, state: &State) -> (Reward, Value) {
+    pub fn evaluate(&self, state: &State, ruleset: &Ruleset) -> (Reward, Value) {
         let (mut reward, mut value) = (0, 0);
 
         let bumpiness = bumpiness(&state.board);
@@ -74,7 +81,9 @@ impl Evaluator {
         value += hight * self.hight;
         value += hight * hight * self.hight_sq;
 
-        value += state.b2b as i32 * self.b2b;
+        value += (state.b2b_chain > 0) as i32 * self.b2b;
+        value += state.b2b_chain as i32 * self.b2b_chain;
+        value += state.pending_garbage() as i32 * self.pending_garbage;
 
         if let Some(hold_piece) = state.hold_piece {
             match hold_piece {
@@ -100,7 +109,7 @@ impl Evaluator {
             }
 
             reward += last_action.perfect_clear as i32 * self.perfect_clear;
-            reward += combo_attack(last_action.combo) as i32 * self.combo_garbage;
+            reward += ruleset.combo_attack(last_action.combo) as i32 * self.combo_garbage;
 
             match last_action.placement_kind {
                 tetris::PlacementKind::Clear1 => reward += self.clear1,
@@ -111,6 +120,11 @@ impl Evaluator {
             }
         }
 
+        // the stack or the garbage that has entered the board blocks the spawn
+        if state.current_piece.is_some() && state.is_dead(ruleset) {
+            value = DEATH;
+        }
+
         (reward, value)
     }
 }
@@ -169,7 +183,7 @@ fn covers(board: &Board) -> i32 {
 
 fn well_depths(board: &Board) -> [i32; 10] {
     let mut well_depths = [0; 10];
-    for x in 0..10 {
+    for (x, well_depth) in well_depths.iter_mut().enumerate() {
         let left = if x == 0 {
             40
         } else {
@@ -180,7 +194,7 @@ fn well_depths(board: &Board) -> [i32; 10] {
         } else {
             board.collumn_heights[x + 1]
         };
-        well_depths[x] = (left.min(right) as i32 - board.collumn_heights[x] as i32).max(0);
+        *well_depth = (left.min(right) as i32 - board.collumn_heights[x] as i32).max(0);
     }
     well_depths
 }
@@ -1,5 +1,5 @@
 use crate::*;
-use tetris::State;
+use tetris::{Placement, Ruleset, State};
 
 // parameter to balance exploration and exploitation
 // a larger value increases randomness in selections
@@ -13,26 +13,33 @@ const UPDATE_RATE: f64 = 0.1;
 
 pub struct Node<'a> {
     pub state: State,
+    pub placement: Option<Placement>, // how the state was reached (None for the root and draws)
     evaluator: &'a Evaluator,
+    ruleset: &'a Ruleset,
     reward: Reward,
     value: Value,
     pub n: u32,
     children: Vec<Node<'a>>,
     max_children_score: Option<Score>,
+    // the next piece is unknown, and each child draws one of the possible pieces
+    is_chance: bool,
 }
 
 impl<'a> Node<'a> {
-    pub fn new(evaluator: &'a Evaluator, state: State) -> Node<'a> {
-        let (reward, value) = evaluator.evaluate(&state);
+    pub fn new(evaluator: &'a Evaluator, ruleset: &'a Ruleset, state: State) -> Node<'a> {
+        let (reward, value) = evaluator.evaluate(&state, ruleset);
 
         Node {
             evaluator,
+            ruleset,
             state,
+            placement: None,
             reward,
             value,
             n: 1,
             children: vec![],
             max_children_score: None,
+            is_chance: false,
         }
     }
 
@@ -56,17 +63,31 @@ impl<'a> Node<'a> {
     }
 
     fn expand(&mut self) {
-        let legal_actions = self.state.legal_actions();
-        for action in legal_actions {
-            let (reward, value) = self.evaluator.evaluate(&action);
+        if self.state.current_piece.is_none() {
+            self.expand_chance();
+            return;
+        }
+        // the game is over, even if the hold piece could spawn
+        if self.state.is_dead(self.ruleset) {
+            return;
+        }
+
+        // the inputs are not needed until a move is chosen
+        // the queued garbage enters the board of the children as it would in the game
+        for placement in self.state.placements(self.ruleset) {
+            let action = self.state.place(&placement, self.ruleset);
+            let (reward, value) = self.evaluator.evaluate(&action, self.ruleset);
             self.children.push(Node {
                 evaluator: self.evaluator,
+                ruleset: self.ruleset,
                 state: action,
+                placement: Some(placement),
                 reward,
                 value,
                 n: 1,
                 children: vec![],
                 max_children_score: None,
+                is_chance: false,
             });
             self.max_children_score = Some(
                 self.max_children_score
@@ -76,13 +97,78 @@ impl<'a> Node<'a> {
         }
     }
 
+    // the preview has run out, so the next piece is inferred from the randomizer
+    fn expand_chance(&mut self) {
+        self.is_chance = true;
+
+        for piece in self.state.possible_next_pieces(self.ruleset) {
+            let mut state = self.state.clone();
+            state.push_next_piece(piece);
+            state.current_piece = state.next_pieces.pop_front();
+
+            // drawing a piece is not an action, so it has no reward and the same board
+            // but the drawn piece can be blocked at the spawn
+            let value = if state.is_dead(self.ruleset) {
+                DEATH
+            } else {
+                self.value
+            };
+            self.children.push(Node {
+                evaluator: self.evaluator,
+                ruleset: self.ruleset,
+                state,
+                placement: None,
+                reward: 0,
+                value,
+                n: 1,
+                children: vec![],
+                max_children_score: None,
+                is_chance: false,
+            });
+        }
+    }
+
+    // the expected score over the possible pieces
+    fn search_chance(&mut self) -> Score {
+        // visit the pieces evenly instead of by ucb
+        let least_visited_child = self
+            .children
+            .iter_mut()
+            .min_by_key(|child| child.n)
+            .unwrap();
+        least_visited_child.search();
+
+        let expected_score = self
+            .children
+            .iter()
+            .map(|child| (child.reward + child.value) as f64)
+            .sum::<f64>()
+            / self.children.len() as f64;
+
+        self.value =
+            ((1.0 - UPDATE_RATE) * (self.value as f64) + UPDATE_RATE * expected_score) as Value;
+        self.n += 1;
+        self.reward + self.value
+    }
+
     pub fn search(&mut self) -> Score {
+        if self.is_chance {
+            return self.search_chance();
+        }
+
         if self.n > 1 && self.children.is_empty() {
             return self.reward + self.value;
         }
 
         let updated_child_score = if self.children.is_empty() {
             self.expand();
+            if self.is_chance {
+                return self.search_chance();
+            }
+            // a dead state keeps its value
+            if self.children.is_empty() {
+                return self.reward + self.value;
+            }
             self.children
                 .iter()
                 .map(|child| child.reward + child.value)
@@ -3,27 +3,32 @@ const EVALUATOR_TIME: u32 = 1000;
 
 #[test]
 fn search_halting() {
-    let state = tetris::State::new_random_state();
+    let state = tetris::State::new_random_state(&mut tetris::Bag::new(1), &mut rand::thread_rng());
 
-    let bot = bot::Bot {
-        evaluator: bot::Evaluator::default(),
-    };
+    let bot = bot::Bot::new(bot::Evaluator::default(), tetris::Ruleset::default());
 
     let next_state = bot
         .get_move_for_repeat(state.clone(), EVALUATOR_REPEAT)
         .unwrap();
 
     assert!(next_state != state);
+
+    // the inputs are only searched for the chosen move
+    let last_action = next_state.last_action.unwrap();
+    assert_eq!(
+        last_action.movements_history.last(),
+        Some(&tetris::PieceMovement::HardDrop)
+    );
 }
 
 // this is not a test, but for checking the display
 #[test]
 fn bot_play_for_count() {
-    let mut current_state = tetris::State::new_random_state();
+    let mut rng = rand::thread_rng();
+    let mut randomizer = tetris::Bag::new(1);
+    let mut current_state = tetris::State::new_random_state(&mut randomizer, &mut rng);
 
-    let bot = bot::Bot {
-        evaluator: bot::Evaluator::default(),
-    };
+    let bot = bot::Bot::new(bot::Evaluator::default(), tetris::Ruleset::default());
 
     loop {
         println!("{}", termion::clear::All);
@@ -33,7 +38,7 @@ fn bot_play_for_count() {
         if let Some(next_state) = bot.get_move_for_repeat(current_state.clone(), EVALUATOR_REPEAT) {
             current_state = next_state.clone();
             if current_state.next_pieces.len() < 8 {
-                current_state.extend_next_pieces();
+                current_state.extend_next_pieces(&mut randomizer, &mut rng);
             }
         } else {
             break;
@@ -44,11 +49,11 @@ fn bot_play_for_count() {
 // this is not a test, but for checking the display
 #[test]
 fn bot_play_for_time() {
-    let mut current_state = tetris::State::new_random_state();
+    let mut rng = rand::thread_rng();
+    let mut randomizer = tetris::Bag::new(1);
+    let mut current_state = tetris::State::new_random_state(&mut randomizer, &mut rng);
 
-    let bot = bot::Bot {
-        evaluator: bot::Evaluator::default(),
-    };
+    let bot = bot::Bot::new(bot::Evaluator::default(), tetris::Ruleset::default());
 
     loop {
         println!("{}", termion::clear::All);
@@ -58,10 +63,115 @@ fn bot_play_for_time() {
         if let Some(next_state) = bot.get_move_for_time(current_state.clone(), EVALUATOR_TIME) {
             current_state = next_state.clone();
             if current_state.next_pieces.len() < 8 {
-                current_state.extend_next_pieces();
+                current_state.extend_next_pieces(&mut randomizer, &mut rng);
             }
         } else {
             break;
         }
     }
 }
+
+#[test]
+fn limited_preview() {
+    let mut rng = tetris::seeded_rng(0);
+    let mut randomizer = tetris::Bag::new(1);
+    let mut state = tetris::State::new_random_state(&mut randomizer, &mut rng);
+    state.extend_next_pieces(&mut randomizer, &mut rng);
+
+    let ruleset = tetris::Ruleset {
+        preview: 2,
+        ..Default::default()
+    };
+    let bot = bot::Bot::new(bot::Evaluator::default(), ruleset);
+
+    let next_state = bot
+        .get_move_for_repeat(state.clone(), EVALUATOR_REPEAT)
+        .unwrap();
+
+    // the pieces hidden from the search are not lost (one piece is placed)
+    assert_eq!(
+        next_state.next_pieces.len() + next_state.hold_piece.is_some() as usize + 1,
+        state.next_pieces.len()
+    );
+    assert_eq!(next_state.next_pieces.back(), state.next_pieces.back());
+}
+
+// the search continues beyond the preview with the pieces left in the bag
+#[test]
+fn search_beyond_preview() {
+    let mut randomizer: tetris::FixedSequence = "TSZJLOI".parse().unwrap();
+    let state = tetris::State::new_random_state(&mut randomizer, &mut rand::thread_rng());
+
+    let ruleset = tetris::Ruleset {
+        preview: 0,
+        ..Default::default()
+    };
+    let bot = bot::Bot::new(bot::Evaluator::default(), ruleset);
+
+    // nothing can be held without a next piece
+    let next_state = bot
+        .get_move_for_repeat(state, EVALUATOR_REPEAT * 10)
+        .unwrap();
+    assert_eq!(next_state.current_piece, Some(tetris::Piece::S));
+    assert_eq!(next_state.hold_piece, None);
+}
+
+// the garbage entering after any placement without a line clear would end the game
+#[test]
+fn survive_incoming_garbage() {
+    let mut state = tetris::State::from_ascii(
+        "
+        current: I
+        next: OOOOO
+        #########.
+        #########.
+        #########.
+        #########.
+        #########.
+        #########.
+        #########.
+        #########.
+        #########.
+        #########.
+        #########.
+        #########.
+        #########.
+        #########.
+        #########.
+        ",
+    )
+    .unwrap();
+    state.garbage_queue.push_back(tetris::Garbage {
+        amount: 8,
+        hole: 0,
+        delay: 0,
+    });
+
+    let ruleset = tetris::Ruleset::default();
+    let bot = bot::Bot::new(bot::Evaluator::default(), ruleset.clone());
+    let next_state = bot.get_move_for_repeat(state, EVALUATOR_REPEAT).unwrap();
+
+    let last_action = next_state.last_action.as_ref().unwrap();
+    assert_eq!(last_action.placement_kind, tetris::PlacementKind::Clear4);
+    assert_eq!(next_state.pending_garbage(), 4);
+    assert!(!next_state.is_dead(&ruleset));
+}
+
+#[test]
+fn top_out_on_a_drawn_piece() {
+    // the spawn rows are full, so every piece that can be drawn is blocked
+    let ruleset = tetris::Ruleset::default();
+    let mut cells = [0; 40];
+    for row in cells.iter_mut().take(23) {
+        *row = 0x3ff & !tetris::row_x(0);
+    }
+    let state = tetris::State {
+        board: tetris::Board::from_cells(cells),
+        current_piece: None,
+        ..tetris::State::from_ascii("current: T").unwrap()
+    };
+
+    let evaluator = bot::Evaluator::default();
+    let mut root = bot::Node::new(&evaluator, &ruleset, state);
+    assert!(root.search() < -50_000);
+}
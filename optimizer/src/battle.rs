@@ -1,152 +1,215 @@
+use rand::{seq::IteratorRandom, Rng};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Reverse, collections::BinaryHeap};
+use tetris::{seeded_rng, GameRng, Randomizer, State, Time};
+
 const MARGIN_TIME: u32 = 1000;
 // gababe increase rate per 1000 time
 const GABAGE_INCREASE: f64 = 1.1;
 
 const EVALUATOR_REPEAT: u32 = 10;
 
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct BattleResult {
     pub attack: u32,
     pub time: u32,
     pub win: bool,
+    pub placement: usize, // 1: the winner, the number of players: the first knocked out
+    pub kos: u32,         // the players knocked out by the garbage of this player
+    pub seed: u64,        // the same seed replays the same battle
 }
 
-// returns true if p1 wins, false if p2 wins
-// urrent implementation has the attack occur at the end of the turn with the attack (just before the next move begins)
-// TODO: allow more accurate simulation of attack timing
-pub fn do_battle(p1: &bot::Bot, p2: &bot::Bot, debug: bool) -> (BattleResult, BattleResult) {
-    let mut p1_attack_sum = 0;
-    let mut p2_attack_sum = 0;
-
-    let mut p1_state = tetris::State::new_random_state();
-    let mut p2_state = tetris::State::new_random_state();
-
-    let mut p1_time = 0;
-    let mut p2_time = 0;
-
-    let mut p1_attack = 0;
-    let mut p2_attack = 0;
-
-    // garbage that a player has not yet received
-    // ex: if p1 sends 2 garbage, p2_garbage will be 2 until p2 receives it
-    let mut p1_garbage = 0;
-    let mut p2_garbage = 0;
-
-    loop {
-        if p1_time <= p2_time {
-            let increase_rate = GABAGE_INCREASE
-                .powf(((p1_time as i32 - MARGIN_TIME as i32).max(0) as f64) / 1000.0);
-            p1_attack = (p1_attack as f64 * increase_rate).round() as u32;
-
-            if p1_garbage as i32 - p1_attack as i32 > 0 {
-                p1_state.receive_garbage(p1_garbage - p1_attack);
-                p1_garbage = 0;
-                p1_attack = 0;
-            } else {
-                p2_garbage += p1_attack - p1_garbage;
-                p1_garbage = 0;
-                p1_attack = 0;
-            }
+// who the attack of a player is sent to (tetris 99)
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default, Serialize, Deserialize)]
+pub enum Targeting {
+    #[default]
+    Random,
+    Attackers, // a player targeting the attacker, or a random player if there are none
+    Kos,       // the player closest to being knocked out
+    Even,      // every player in turn
+}
 
-            if p1_state.is_dead() {
-                return (
-                    BattleResult {
-                        attack: p1_attack_sum,
-                        time: p1_time,
-                        win: false,
-                    },
-                    BattleResult {
-                        attack: p2_attack_sum,
-                        time: p2_time,
-                        win: true,
-                    },
-                );
-            }
+impl Targeting {
+    pub fn from_name(name: &str) -> Option<Targeting> {
+        match name {
+            "random" => Some(Targeting::Random),
+            "attackers" => Some(Targeting::Attackers),
+            "kos" => Some(Targeting::Kos),
+            "even" => Some(Targeting::Even),
+            _ => None,
+        }
+    }
+}
 
-            if let Some(new_state) = p1.get_move_for_repeat(p1_state.clone(), EVALUATOR_REPEAT) {
-                p1_state = new_state;
-                if p1_state.next_pieces.len() < 8 {
-                    p1_state.extend_next_pieces();
-                }
-            } else {
-                return (
-                    BattleResult {
-                        attack: p1_attack_sum,
-                        time: p1_time,
-                        win: false,
-                    },
-                    BattleResult {
-                        attack: p2_attack_sum,
-                        time: p2_time,
-                        win: true,
-                    },
-                );
-            }
+struct Player<'a> {
+    bot: &'a bot::Bot,
+    state: State,
+    rng: GameRng,         // for the pieces
+    garbage_rng: GameRng, // for the holes of the garbage received
+    randomizer: Box<dyn Randomizer + Send>,
+    time: Time,
+    attack: u32,
+    target: Option<usize>,
+    last_attacker: Option<usize>, // credited with the ko
+    placement: Option<usize>,     // None while alive
+    kos: u32,
+}
 
-            p1_time += p1_state.last_action.clone().unwrap().time;
-
-            let garbage_sent = p1_state.last_action.clone().unwrap().garbage_sent;
-            p1_attack += garbage_sent;
-            p1_attack_sum += garbage_sent;
-        } else {
-            let increase_rate = GABAGE_INCREASE
-                .powf(((p2_time as i32 - MARGIN_TIME as i32).max(0) as f64) / 1000.0);
-            p2_attack = (p2_attack as f64 * increase_rate).round() as u32;
-
-            if p2_garbage as i32 - p2_attack as i32 > 0 {
-                p2_state.receive_garbage(p2_garbage - p2_attack);
-                p2_garbage = 0;
-                p2_attack = 0;
-            } else {
-                p1_garbage += p2_attack - p2_garbage;
-                p2_garbage = 0;
-                p2_attack = 0;
-            }
+impl Player<'_> {
+    // the stack and the garbage on the way
+    fn danger(&self) -> u32 {
+        let height = *self.state.board.collumn_heights.iter().max().unwrap();
+        height + self.state.pending_garbage()
+    }
+}
 
-            if p2_state.is_dead() {
-                return (
-                    BattleResult {
-                        attack: p1_attack_sum,
-                        time: p1_time,
-                        win: true,
-                    },
-                    BattleResult {
-                        attack: p2_attack_sum,
-                        time: p2_time,
-                        win: false,
-                    },
-                );
+// the player with the least time moves next, and the attack is queued to the target
+// when the piece is placed, where it travels with the delay of the rules
+pub fn do_battle(
+    bots: &[&bot::Bot],
+    ruleset: &tetris::Ruleset,
+    targeting: Targeting,
+    seed: u64,
+    debug: bool,
+) -> Vec<BattleResult> {
+    assert!(bots.len() >= 2, "a battle needs at least two players");
+
+    // each player has its own rngs, so that the pieces of one player do not depend on the garbage of the others
+    let mut rng = seeded_rng(seed);
+    let mut players: Vec<_> = bots
+        .iter()
+        .map(|&bot| {
+            let garbage_rng = seeded_rng(rng.gen());
+            let mut rng = seeded_rng(rng.gen());
+            let mut randomizer = ruleset
+                .randomizer
+                .build()
+                .expect("the ruleset should be validated");
+            let state = State::new_random_state(&mut randomizer, &mut rng);
+            Player {
+                bot,
+                state,
+                rng,
+                garbage_rng,
+                randomizer,
+                time: 0,
+                attack: 0,
+                target: None,
+                last_attacker: None,
+                placement: None,
+                kos: 0,
             }
-
-            if let Some(new_state) = p2.get_move_for_repeat(p2_state.clone(), EVALUATOR_REPEAT) {
-                p2_state = new_state;
-                if p2_state.next_pieces.len() < 8 {
-                    p2_state.extend_next_pieces();
-                }
-            } else {
-                return (
-                    BattleResult {
-                        attack: p1_attack_sum,
-                        time: p1_time,
-                        win: true,
-                    },
-                    BattleResult {
-                        attack: p2_attack_sum,
-                        time: p2_time,
-                        win: false,
-                    },
-                );
+        })
+        .collect();
+
+    // ties go to the player listed first
+    let mut timeline: BinaryHeap<_> = (0..players.len()).map(|i| Reverse((0, i))).collect();
+
+    while timeline.len() > 1 {
+        let Reverse((_, i)) = timeline.pop().unwrap();
+
+        let player = &mut players[i];
+        let new_state = Some(&player.state)
+            .filter(|state| !state.is_dead(ruleset))
+            .and_then(|state| {
+                player
+                    .bot
+                    .get_move_for_repeat(state.clone(), EVALUATOR_REPEAT)
+            });
+        let Some(new_state) = new_state else {
+            player.placement = Some(timeline.len() + 1);
+            // an attacker knocked out before is not credited
+            let last_attacker = player.last_attacker;
+            if let Some(attacker) = last_attacker.filter(|&j| players[j].placement.is_none()) {
+                players[attacker].kos += 1;
             }
+            continue;
+        };
+
+        player.state = new_state;
+        if player.state.next_pieces.len() < 8 {
+            player
+                .state
+                .extend_next_pieces(&mut player.randomizer, &mut player.rng);
+        }
 
-            p2_time += p2_state.last_action.clone().unwrap().time;
-
-            let garbage_sent = p2_state.last_action.clone().unwrap().garbage_sent;
-            p2_attack += garbage_sent;
-            p2_attack_sum += garbage_sent;
+        let last_action = player.state.last_action.clone().unwrap();
+        player.time += last_action.time;
+        player.attack += last_action.garbage_sent;
+        let time = player.time;
+        timeline.push(Reverse((time, i)));
+
+        // the attack left after cancelling, which arrives later if the target is behind in time
+        // and sooner if it is ahead
+        let increase_rate =
+            GABAGE_INCREASE.powf(((time as i32 - MARGIN_TIME as i32).max(0) as f64) / 1000.0);
+        let attack = ((last_action.garbage_sent - last_action.garbage_cancelled) as f64
+            * increase_rate)
+            .round() as u32;
+
+        if let Some(target) = choose_target(&players, i, targeting, &mut rng) {
+            players[i].target = Some(target);
+            if attack > 0 {
+                let target = &mut players[target];
+                let lead = time as i32 - target.time as i32;
+                target
+                    .state
+                    .queue_garbage(attack, lead, ruleset, &mut target.garbage_rng);
+                target.last_attacker = Some(i);
+            }
         }
 
         if debug {
+            let states = players.iter().map(|player| player.state.clone()).collect();
             println!("{}", termion::clear::All);
-            println!("{}", tetris::PairState(p1_state.clone(), p2_state.clone()));
+            println!("{}", tetris::States(states));
+        }
+    }
+
+    players
+        .iter()
+        .map(|player| {
+            let placement = player.placement.unwrap_or(1);
+            BattleResult {
+                attack: player.attack,
+                time: player.time,
+                win: placement == 1,
+                placement,
+                kos: player.kos,
+                seed,
+            }
+        })
+        .collect()
+}
+
+fn choose_target(
+    players: &[Player],
+    attacker: usize,
+    targeting: Targeting,
+    rng: &mut impl Rng,
+) -> Option<usize> {
+    let opponents = (0..players.len()).filter(|&i| i != attacker && players[i].placement.is_none());
+
+    match targeting {
+        Targeting::Random => opponents.choose(rng),
+        Targeting::Attackers => {
+            let attackers: Vec<_> = opponents
+                .clone()
+                .filter(|&i| players[i].target == Some(attacker))
+                .collect();
+            attackers
+                .into_iter()
+                .choose(rng)
+                .or_else(|| opponents.choose(rng))
+        }
+        Targeting::Kos => opponents.max_by_key(|&i| (players[i].danger(), Reverse(i))),
+        Targeting::Even => {
+            // the next player after the last target
+            let last = players[attacker].target.unwrap_or(attacker);
+            opponents
+                .clone()
+                .find(|&i| i > last)
+                .or_else(|| opponents.min())
         }
     }
 }
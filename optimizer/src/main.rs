@@ -11,13 +11,41 @@ pub use score::*;
 pub use threadpool::*;
 
 fn main() {
-    let path = "population.json";
+    // usage: optimizer [ruleset.json | guideline | tetrio | jstris | ppt | tetris99] [players per battle]
+    //                  [random | attackers | kos | even]
+    // each ruleset is optimized in its own population file, so that they can be compared side by side
+    let (ruleset, path) = match std::env::args().nth(1) {
+        Some(ruleset_path) => {
+            let name = std::path::Path::new(&ruleset_path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            (
+                load_ruleset(&ruleset_path).unwrap_or_else(|error| panic!("{}", error)),
+                format!("population_{}.json", name),
+            )
+        }
+        None => (tetris::Ruleset::default(), "population.json".to_string()),
+    };
 
-    let mut population = Population::load_or_generate(path);
+    let mut population =
+        Population::load_or_generate(&path).unwrap_or_else(|error| panic!("{}", error));
+    // NOTE: the players and the targeting are saved with the population
+    if let Some(room_size) = std::env::args().nth(2) {
+        population.room_size = room_size
+            .parse()
+            .ok()
+            .filter(|&room_size| room_size >= 2)
+            .unwrap_or_else(|| panic!("{}: not a number of players", room_size));
+    }
+    if let Some(targeting) = std::env::args().nth(3) {
+        population.targeting = Targeting::from_name(&targeting)
+            .unwrap_or_else(|| panic!("{}: not a targeting", targeting));
+    }
 
     loop {
-        let new_population = population.optimize();
-        population.save(path);
+        let new_population = population.optimize(&ruleset);
+        population.save(&path);
         population = new_population;
     }
 }
@@ -1,19 +1,17 @@
 use bot::Evaluator;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 pub trait Gene {
-    fn generate() -> Self;
-    fn crossover(parent1: &Self, parent2: &Self) -> Self;
+    fn generate(rng: &mut impl Rng) -> Self;
+    fn crossover(parent1: &Self, parent2: &Self, rng: &mut impl Rng) -> Self;
 }
 
 impl Gene for i32 {
-    fn generate() -> Self {
-        thread_rng().gen_range(-30..=30)
+    fn generate(rng: &mut impl Rng) -> Self {
+        rng.gen_range(-30..=30)
     }
 
-    fn crossover(v1: &Self, v2: &Self) -> i32 {
-        let mut rng = thread_rng();
-
+    fn crossover(v1: &Self, v2: &Self, rng: &mut impl Rng) -> i32 {
         (match rng.gen_range(0..100) {
             0..=41 => *v1,                  // 42%
             42..=83 => *v2,                 // 42%
@@ -24,84 +22,105 @@ impl Gene for i32 {
 }
 
 impl Gene for Evaluator {
-    fn generate() -> Self {
+    fn generate(rng: &mut impl Rng) -> Self {
         Evaluator {
-            bumpiness: i32::generate(),
-            bumpiness_sq: i32::generate(),
-            cavities: i32::generate(),
-            cavities_sq: i32::generate(),
-            overhangs: i32::generate(),
-            overhangs_sq: i32::generate(),
-            covers: i32::generate(),
-            covers_sq: i32::generate(),
-            well_depth_1: i32::generate(),
-            well_depth_1_sq: i32::generate(),
-            well_depth_2: i32::generate(),
-            well_depth_2_sq: i32::generate(),
-            clearable_lines: i32::generate(),
-            clearable_lines_sq: i32::generate(),
-            hight: i32::generate(),
-            hight_sq: i32::generate(),
-            b2b: i32::generate(),
+            bumpiness: i32::generate(rng),
+            bumpiness_sq: i32::generate(rng),
+            cavities: i32::generate(rng),
+            cavities_sq: i32::generate(rng),
+            overhangs: i32::generate(rng),
+            overhangs_sq: i32::generate(rng),
+            covers: i32::generate(rng),
+            covers_sq: i32::generate(rng),
+            well_depth_1: i32::generate(rng),
+            well_depth_1_sq: i32::generate(rng),
+            well_depth_2: i32::generate(rng),
+            well_depth_2_sq: i32::generate(rng),
+            clearable_lines: i32::generate(rng),
+            clearable_lines_sq: i32::generate(rng),
+            hight: i32::generate(rng),
+            hight_sq: i32::generate(rng),
+            b2b: i32::generate(rng),
+            b2b_chain: i32::generate(rng),
+            pending_garbage: i32::generate(rng),
             holding: [
-                i32::generate(),
-                i32::generate(),
-                i32::generate(),
-                i32::generate(),
-                i32::generate(),
+                i32::generate(rng),
+                i32::generate(rng),
+                i32::generate(rng),
+                i32::generate(rng),
+                i32::generate(rng),
             ],
 
-            move_time: i32::generate(),
-            wasted_i: i32::generate(),
-            b2b_clear: i32::generate(),
-            perfect_clear: i32::generate(),
-            combo_garbage: i32::generate(),
-            clear1: i32::generate(),
-            clear2: i32::generate(),
-            clear3: i32::generate(),
-            clear4: i32::generate(),
+            move_time: i32::generate(rng),
+            wasted_i: i32::generate(rng),
+            b2b_clear: i32::generate(rng),
+            perfect_clear: i32::generate(rng),
+            combo_garbage: i32::generate(rng),
+            clear1: i32::generate(rng),
+            clear2: i32::generate(rng),
+            clear3: i32::generate(rng),
+            clear4: i32::generate(rng),
         }
     }
 
-    fn crossover(parent1: &Self, parent2: &Self) -> Self {
+    fn crossover(parent1: &Self, parent2: &Self, rng: &mut impl Rng) -> Self {
         Evaluator {
-            bumpiness: i32::crossover(&parent1.bumpiness, &parent2.bumpiness),
-            bumpiness_sq: i32::crossover(&parent1.bumpiness_sq, &parent2.bumpiness_sq),
-            cavities: i32::crossover(&parent1.cavities, &parent2.cavities),
-            cavities_sq: i32::crossover(&parent1.cavities_sq, &parent2.cavities_sq),
-            overhangs: i32::crossover(&parent1.overhangs, &parent2.overhangs),
-            overhangs_sq: i32::crossover(&parent1.overhangs_sq, &parent2.overhangs_sq),
-            covers: i32::crossover(&parent1.covers, &parent2.covers),
-            covers_sq: i32::crossover(&parent1.covers_sq, &parent2.covers_sq),
-            well_depth_1: i32::crossover(&parent1.well_depth_1, &parent2.well_depth_1),
-            well_depth_1_sq: i32::crossover(&parent1.well_depth_1_sq, &parent2.well_depth_1_sq),
-            well_depth_2: i32::crossover(&parent1.well_depth_2, &parent2.well_depth_2),
-            well_depth_2_sq: i32::crossover(&parent1.well_depth_2_sq, &parent2.well_depth_2_sq),
-            clearable_lines: i32::crossover(&parent1.clearable_lines, &parent2.clearable_lines),
+            bumpiness: i32::crossover(&parent1.bumpiness, &parent2.bumpiness, rng),
+            bumpiness_sq: i32::crossover(&parent1.bumpiness_sq, &parent2.bumpiness_sq, rng),
+            cavities: i32::crossover(&parent1.cavities, &parent2.cavities, rng),
+            cavities_sq: i32::crossover(&parent1.cavities_sq, &parent2.cavities_sq, rng),
+            overhangs: i32::crossover(&parent1.overhangs, &parent2.overhangs, rng),
+            overhangs_sq: i32::crossover(&parent1.overhangs_sq, &parent2.overhangs_sq, rng),
+            covers: i32::crossover(&parent1.covers, &parent2.covers, rng),
+            covers_sq: i32::crossover(&parent1.covers_sq, &parent2.covers_sq, rng),
+            well_depth_1: i32::crossover(&parent1.well_depth_1, &parent2.well_depth_1, rng),
+            well_depth_1_sq: i32::crossover(
+                &parent1.well_depth_1_sq,
+                &parent2.well_depth_1_sq,
+                rng,
+            ),
+            well_depth_2: i32::crossover(&parent1.well_depth_2, &parent2.well_depth_2, rng),
+            well_depth_2_sq: i32::crossover(
+                &parent1.well_depth_2_sq,
+                &parent2.well_depth_2_sq,
+                rng,
+            ),
+            clearable_lines: i32::crossover(
+                &parent1.clearable_lines,
+                &parent2.clearable_lines,
+                rng,
+            ),
             clearable_lines_sq: i32::crossover(
                 &parent1.clearable_lines_sq,
                 &parent2.clearable_lines_sq,
+                rng,
+            ),
+            hight: i32::crossover(&parent1.hight, &parent2.hight, rng),
+            hight_sq: i32::crossover(&parent1.hight_sq, &parent2.hight_sq, rng),
+            b2b: i32::crossover(&parent1.b2b, &parent2.b2b, rng),
+            b2b_chain: i32::crossover(&parent1.b2b_chain, &parent2.b2b_chain, rng),
+            pending_garbage: i32::crossover(
+                &parent1.pending_garbage,
+                &parent2.pending_garbage,
+                rng,
             ),
-            hight: i32::crossover(&parent1.hight, &parent2.hight),
-            hight_sq: i32::crossover(&parent1.hight_sq, &parent2.hight_sq),
-            b2b: i32::crossover(&parent1.b2b, &parent2.b2b),
             holding: [
-                i32::crossover(&parent1.holding[0], &parent2.holding[0]),
-                i32::crossover(&parent1.holding[1], &parent2.holding[1]),
-                i32::crossover(&parent1.holding[2], &parent2.holding[2]),
-                i32::crossover(&parent1.holding[3], &parent2.holding[3]),
-                i32::crossover(&parent1.holding[4], &parent2.holding[4]),
+                i32::crossover(&parent1.holding[0], &parent2.holding[0], rng),
+                i32::crossover(&parent1.holding[1], &parent2.holding[1], rng),
+                i32::crossover(&parent1.holding[2], &parent2.holding[2], rng),
+                i32::crossover(&parent1.holding[3], &parent2.holding[3], rng),
+                i32::crossover(&parent1.holding[4], &parent2.holding[4], rng),
             ],
 
-            move_time: i32::crossover(&parent1.move_time, &parent2.move_time),
-            wasted_i: i32::crossover(&parent1.wasted_i, &parent2.wasted_i),
-            b2b_clear: i32::crossover(&parent1.b2b_clear, &parent2.b2b_clear),
-            perfect_clear: i32::crossover(&parent1.perfect_clear, &parent2.perfect_clear),
-            combo_garbage: i32::crossover(&parent1.combo_garbage, &parent2.combo_garbage),
-            clear1: i32::crossover(&parent1.clear1, &parent2.clear1),
-            clear2: i32::crossover(&parent1.clear2, &parent2.clear2),
-            clear3: i32::crossover(&parent1.clear3, &parent2.clear3),
-            clear4: i32::crossover(&parent1.clear4, &parent2.clear4),
+            move_time: i32::crossover(&parent1.move_time, &parent2.move_time, rng),
+            wasted_i: i32::crossover(&parent1.wasted_i, &parent2.wasted_i, rng),
+            b2b_clear: i32::crossover(&parent1.b2b_clear, &parent2.b2b_clear, rng),
+            perfect_clear: i32::crossover(&parent1.perfect_clear, &parent2.perfect_clear, rng),
+            combo_garbage: i32::crossover(&parent1.combo_garbage, &parent2.combo_garbage, rng),
+            clear1: i32::crossover(&parent1.clear1, &parent2.clear1, rng),
+            clear2: i32::crossover(&parent1.clear2, &parent2.clear2, rng),
+            clear3: i32::crossover(&parent1.clear3, &parent2.clear3, rng),
+            clear4: i32::crossover(&parent1.clear4, &parent2.clear4, rng),
         }
     }
 }
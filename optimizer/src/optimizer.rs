@@ -3,7 +3,7 @@ use bot::{Bot, Evaluator};
 use itertools::Itertools;
 use rand::{
     seq::{IteratorRandom, SliceRandom},
-    thread_rng,
+    thread_rng, Rng,
 };
 use serde::{Deserialize, Serialize};
 use std::{
@@ -11,6 +11,7 @@ use std::{
     io::{Read, Write},
     sync::{Arc, Mutex},
 };
+use tetris::{seeded_rng, Ruleset};
 
 // adjust the diversity of the population
 const POPULATION_SIZE: usize = 300;
@@ -19,9 +20,21 @@ const MATCH_COUNT: usize = 8;
 // adjust selection pressure
 const SELECTION_SIZE: usize = 20;
 
+fn default_room_size() -> usize {
+    2
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct Population {
     pub generation: u32,
+    // every battle and crossover of the generation is derived from this seed
+    #[serde(default)]
+    pub seed: u64,
+    // the players in each battle, and who their attacks are sent to
+    #[serde(default = "default_room_size")]
+    pub room_size: usize,
+    #[serde(default)]
+    pub targeting: Targeting,
     pub members: Vec<Member>,
 }
 
@@ -29,31 +42,53 @@ pub struct Population {
 pub struct Member {
     pub evaluator: Evaluator,
     pub score: Score,
+    #[serde(default)]
+    pub battles: Vec<BattleRecord>,
+}
+
+// enough to replay a battle with `do_battle`
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct BattleRecord {
+    // the members in the order of the players, including this member
+    // NOTE: empty in the records of the 1v1 versions
+    #[serde(default)]
+    pub room: Vec<usize>,
+    pub seed: u64,
+    #[serde(default)]
+    pub placement: usize,
+    pub win: bool,
 }
 
 impl Population {
-    fn generate() -> Self {
+    pub fn generate(seed: u64) -> Self {
+        let mut rng = seeded_rng(seed);
         let mut members = vec![];
         for _ in 0..POPULATION_SIZE {
             members.push(Member {
-                evaluator: Evaluator::generate(),
+                evaluator: Evaluator::generate(&mut rng),
                 score: Score::new(),
+                battles: vec![],
             });
         }
 
         Population {
             generation: 0,
+            seed: rng.gen(),
+            room_size: default_room_size(),
+            targeting: Targeting::default(),
             members,
         }
     }
 
-    pub fn load_or_generate(path: &str) -> Self {
+    // a new population is generated only if there is no file, so that a broken one is not overwritten
+    pub fn load_or_generate(path: &str) -> Result<Self, String> {
         if let Ok(mut file) = File::open(path) {
             let mut json = String::new();
-            let _ = file.read_to_string(&mut json);
-            serde_json::from_str(&json).unwrap_or_else(|_| Self::generate())
+            file.read_to_string(&mut json)
+                .map_err(|error| format!("{}: {}", path, error))?;
+            serde_json::from_str(&json).map_err(|error| format!("{}: {}", path, error))
         } else {
-            Self::generate()
+            Ok(Self::generate(thread_rng().gen()))
         }
     }
 
@@ -63,43 +98,66 @@ impl Population {
         file.write_all(json.as_bytes()).unwrap();
     }
 
-    fn evaluate(&mut self) {
+    fn evaluate(&mut self, ruleset: &Ruleset) {
         let members: Arc<Vec<Mutex<Member>>> =
             Arc::new(self.members.clone().into_iter().map(Mutex::new).collect());
 
-        let generation = self.generation;
+        let (generation, room_size, targeting) = (self.generation, self.room_size, self.targeting);
         let count = Arc::new(Mutex::new(0));
 
+        // seeds are drawn before spawning, so that the battles do not depend on the thread scheduling
+        let mut rng = seeded_rng(self.seed);
+        let member_seeds: Vec<u64> = (0..POPULATION_SIZE).map(|_| rng.gen()).collect();
+
         {
             let thread_num = num_cpus::get();
             let pool = ThreadPool::new(thread_num);
 
-            for i in 0..POPULATION_SIZE {
+            for (i, &seed) in member_seeds.iter().enumerate() {
                 let members = Arc::clone(&members);
                 let count = Arc::clone(&count);
+                let ruleset = ruleset.clone();
 
                 pool.execute(move || {
-                    let mut rng = thread_rng();
-                    let opponents_index =
-                        (0..POPULATION_SIZE).choose_multiple(&mut rng, MATCH_COUNT);
-
-                    for j in opponents_index {
-                        {
-                            let p1 = Bot::new(members[i].lock().unwrap().evaluator);
-                            let p2 = Bot::new(members[j].lock().unwrap().evaluator);
-
-                            let (p1, p2) = do_battle(&p1, &p2, false);
-
-                            members[i]
-                                .lock()
-                                .unwrap()
-                                .score
-                                .update(p1.attack, p1.time, p1.win);
-                            members[j]
-                                .lock()
-                                .unwrap()
-                                .score
-                                .update(p2.attack, p2.time, p2.win);
+                    let mut rng = seeded_rng(seed);
+
+                    for _ in 0..MATCH_COUNT {
+                        // this member is the first player of each room
+                        let mut room = vec![i];
+                        room.extend(
+                            (0..POPULATION_SIZE)
+                                .filter(|&j| j != i)
+                                .choose_multiple(&mut rng, room_size - 1),
+                        );
+
+                        let bots: Vec<_> = room
+                            .iter()
+                            .map(|&j| {
+                                Bot::new(members[j].lock().unwrap().evaluator, ruleset.clone())
+                            })
+                            .collect();
+                        let results = do_battle(
+                            &bots.iter().collect_vec(),
+                            &ruleset,
+                            targeting,
+                            rng.gen(),
+                            false,
+                        );
+
+                        for (&j, result) in room.iter().zip(results.iter()) {
+                            let mut member = members[j].lock().unwrap();
+                            member.score.update(
+                                result.attack,
+                                result.time,
+                                result.placement,
+                                room.len(),
+                            );
+                            member.battles.push(BattleRecord {
+                                room: room.clone(),
+                                seed: result.seed,
+                                placement: result.placement,
+                                win: result.win,
+                            });
                         }
                     }
 
@@ -113,9 +171,8 @@ impl Population {
         self.members = members.iter().map(|m| m.lock().unwrap().clone()).collect();
     }
 
-    fn select(&self) -> (&Member, &Member) {
-        let mut rng = thread_rng();
-        let group = self.members.choose_multiple(&mut rng, SELECTION_SIZE);
+    fn select(&self, rng: &mut impl Rng) -> (&Member, &Member) {
+        let group = self.members.choose_multiple(rng, SELECTION_SIZE);
         group
             .sorted_by(|a, b| b.score.cmp(&a.score))
             .take(2)
@@ -124,37 +181,64 @@ impl Population {
     }
 
     fn crossover(&self) -> Self {
+        // a different stream from the one used in `evaluate`
+        let mut rng = seeded_rng(!self.seed);
+
         // elite
         let mut new_members: Vec<_> = self
             .members
             .iter()
             .sorted_by(|a, b| b.score.cmp(&a.score))
             .take(2)
-            .cloned()
+            .map(|member| Member {
+                battles: vec![],
+                ..member.clone()
+            })
             .collect();
 
         // crossover
         while new_members.len() < POPULATION_SIZE {
-            let (parent1, parent2) = self.select();
-            let evaluator = Evaluator::crossover(&parent1.evaluator, &parent2.evaluator);
+            let (parent1, parent2) = self.select(&mut rng);
+            let evaluator = Evaluator::crossover(&parent1.evaluator, &parent2.evaluator, &mut rng);
             new_members.push(Member {
                 evaluator,
                 score: Score::new(),
+                battles: vec![],
             });
         }
 
         Population {
             generation: self.generation + 1,
+            seed: rng.gen(),
             members: new_members,
+            ..*self
         }
     }
 
-    pub fn optimize(&mut self) -> Self {
-        self.evaluate();
+    pub fn optimize(&mut self, ruleset: &Ruleset) -> Self {
+        self.evaluate(ruleset);
         self.crossover()
     }
 }
 
+// `path` is either a json file or the name of a preset (e.g. "tetrio")
+pub fn load_ruleset(path: &str) -> Result<Ruleset, String> {
+    if let Ok(mut file) = File::open(path) {
+        let mut json = String::new();
+        file.read_to_string(&mut json)
+            .map_err(|error| format!("{}: {}", path, error))?;
+        let ruleset: Ruleset =
+            serde_json::from_str(&json).map_err(|error| format!("{}: {}", path, error))?;
+        ruleset
+            .validate()
+            .map_err(|error| format!("{}: {}", path, error))?;
+        Ok(ruleset)
+    } else {
+        Ruleset::from_preset(path)
+            .ok_or_else(|| format!("{}: neither a ruleset file nor a preset", path))
+    }
+}
+
 fn debug_optimizer(generation: u32, member: usize) {
     println!("{}", termion::cursor::Show);
     println!("generation: {}", generation);
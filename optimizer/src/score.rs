@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 
-// the attenuation rate of attack when losing
+// the attenuation rate of attack when finishing last
+// (the places between the first and the last are in proportion)
 const LOSE_RATE: f64 = 0.5;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Default, Serialize, Deserialize)]
@@ -14,13 +15,11 @@ impl Score {
         Score { time: 0, attack: 0 }
     }
 
-    pub fn update(&mut self, attack: u32, time: u32, win: bool) {
+    // placement: 1 for the winner, up to the number of players
+    pub fn update(&mut self, attack: u32, time: u32, placement: usize, players: usize) {
         self.time += time;
-        self.attack += if win {
-            attack
-        } else {
-            (attack as f64 * LOSE_RATE) as u32
-        };
+        let rate = 1.0 - (1.0 - LOSE_RATE) * (placement - 1) as f64 / (players - 1) as f64;
+        self.attack += (attack as f64 * rate) as u32;
     }
 
     fn attack_per_time(&self) -> f64 {
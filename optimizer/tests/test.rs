@@ -7,7 +7,7 @@ const EVALUATOR_REPEAT: u32 = 10;
 fn replay_population() {
     let path = "../population.json";
 
-    let population = Population::load_or_generate(path);
+    let population = Population::load_or_generate(path).unwrap();
 
     let (p1, p2) = population
         .members
@@ -17,22 +17,61 @@ fn replay_population() {
         .next_tuple()
         .unwrap();
 
-    let p1 = bot::Bot::new(p1.evaluator);
-    let p2 = bot::Bot::new(p2.evaluator);
+    let p1 = bot::Bot::new(p1.evaluator, tetris::Ruleset::default());
+    let p2 = bot::Bot::new(p2.evaluator, tetris::Ruleset::default());
 
-    optimizer::do_battle(&p1, &p2, true);
+    optimizer::do_battle(
+        &[&p1, &p2],
+        &tetris::Ruleset::default(),
+        Targeting::Random,
+        rand::random(),
+        true,
+    );
+}
+
+// this is not a test, but for replaying the worst loss of the best member
+#[test]
+fn replay_loss() {
+    let path = "../population.json";
+
+    let population = Population::load_or_generate(path).unwrap();
+
+    let member = population.members.iter().max_by_key(|m| &m.score).unwrap();
+    let Some(record) = member.battles.iter().find(|record| !record.win) else {
+        return;
+    };
+
+    let bots: Vec<_> = record
+        .room
+        .iter()
+        .map(|&j| bot::Bot::new(population.members[j].evaluator, tetris::Ruleset::default()))
+        .collect();
+    if bots.len() < 2 {
+        return;
+    }
+
+    optimizer::do_battle(
+        &bots.iter().collect_vec(),
+        &tetris::Ruleset::default(),
+        population.targeting,
+        record.seed,
+        true,
+    );
 }
 
 #[test]
 fn replay_bot() {
     let path = "../population.json";
 
-    let population = Population::load_or_generate(path);
+    let population = Population::load_or_generate(path).unwrap();
 
     let bot = population.members.iter().max_by_key(|m| &m.score).unwrap();
-    let bot = bot::Bot::new(bot.evaluator);
+    let bot = bot::Bot::new(bot.evaluator, tetris::Ruleset::default());
+
+    let mut rng = tetris::seeded_rng(population.seed);
 
-    let mut current_state = tetris::State::new_random_state();
+    let mut randomizer = tetris::Bag::new(1);
+    let mut current_state = tetris::State::new_random_state(&mut randomizer, &mut rng);
 
     loop {
         println!("{}", termion::clear::All);
@@ -42,10 +81,134 @@ fn replay_bot() {
         if let Some(next_state) = bot.get_move_for_repeat(current_state.clone(), EVALUATOR_REPEAT) {
             current_state = next_state.clone();
             if current_state.next_pieces.len() < 8 {
-                current_state.extend_next_pieces();
+                current_state.extend_next_pieces(&mut randomizer, &mut rng);
             }
         } else {
             break;
         }
     }
 }
+
+#[test]
+fn partial_ruleset() {
+    let ruleset: tetris::Ruleset =
+        serde_json::from_str(r#"{ "rotate_180": true, "combo_table": { "Table": [0, 1, 2] } }"#)
+            .unwrap();
+
+    assert!(ruleset.rotate_180);
+    assert_eq!(ruleset.combo_attack(10), 2);
+    assert_eq!(ruleset.attack_table, tetris::AttackTable::default());
+
+    assert_eq!(load_ruleset("tetrio"), Ok(tetris::Ruleset::tetrio()));
+    // a typo is not the default ruleset
+    assert!(load_ruleset("tetiro").is_err());
+
+    // an invalid fixed sequence is not replaced with another one
+    let path = std::env::temp_dir().join("artemis_invalid_ruleset.json");
+    std::fs::write(&path, r#"{ "randomizer": { "Fixed": "TIX" } }"#).unwrap();
+    let error = load_ruleset(path.to_str().unwrap()).unwrap_err();
+    assert!(error.contains("'X'"));
+}
+
+#[test]
+fn seeded_population() {
+    assert_eq!(Population::generate(7), Population::generate(7));
+    assert_ne!(Population::generate(7), Population::generate(8));
+}
+
+#[test]
+fn broken_population() {
+    // a population file that cannot be read is reported, not replaced with a new population
+    let path = std::env::temp_dir().join("artemis_broken_population.json");
+    std::fs::write(&path, "{ \"generation\": ").unwrap();
+    assert!(Population::load_or_generate(path.to_str().unwrap()).is_err());
+
+    let path = std::env::temp_dir().join("artemis_missing_population.json");
+    let _ = std::fs::remove_file(&path);
+    assert!(Population::load_or_generate(path.to_str().unwrap()).is_ok());
+
+    assert_eq!(Targeting::from_name("kos"), Some(Targeting::Kos));
+    assert_eq!(Targeting::from_name("ko"), None);
+}
+
+#[test]
+fn state_json() {
+    let ruleset = tetris::Ruleset::default();
+    let mut rng = tetris::seeded_rng(0);
+    let mut randomizer = tetris::Bag::new(1);
+    let mut state = tetris::State::new_random_state(&mut randomizer, &mut rng);
+    state.receive_garbage(4, &ruleset, &mut rng);
+    let state = state.legal_actions(&ruleset).swap_remove(0);
+
+    let json = serde_json::to_string(&state).unwrap();
+    assert_eq!(serde_json::from_str::<tetris::State>(&json).unwrap(), state);
+
+    // a board has exactly 40 rows
+    assert!(serde_json::from_str::<tetris::Board>(
+        r#"{ "cells": [0], "collumn_heights": [0, 0, 0, 0, 0, 0, 0, 0, 0, 0] }"#
+    )
+    .is_err());
+}
+
+#[test]
+fn battle_room() {
+    // stacks up to end the battle soon
+    let evaluator = bot::Evaluator {
+        hight: 10,
+        ..Default::default()
+    };
+    let bot = bot::Bot::new(evaluator, tetris::Ruleset::default());
+
+    for targeting in [Targeting::Even, Targeting::Kos] {
+        let results = do_battle(
+            &[&bot, &bot, &bot],
+            &tetris::Ruleset::default(),
+            targeting,
+            0,
+            false,
+        );
+
+        // every player has a different place, and the winner is the last one standing
+        let mut placements: Vec<_> = results.iter().map(|result| result.placement).collect();
+        placements.sort();
+        assert_eq!(placements, vec![1, 2, 3]);
+        assert_eq!(results.iter().filter(|result| result.win).count(), 1);
+        assert!(results.iter().map(|result| result.kos).sum::<u32>() <= 2);
+        // a knocked out player does not knock out anyone
+        let last = results.iter().find(|result| result.placement == 3).unwrap();
+        assert_eq!(last.kos, 0);
+    }
+}
+
+#[test]
+#[should_panic]
+fn battle_alone() {
+    let bot = bot::Bot::new(bot::Evaluator::default(), tetris::Ruleset::default());
+    do_battle(
+        &[&bot],
+        &tetris::Ruleset::default(),
+        Targeting::Random,
+        0,
+        false,
+    );
+}
+
+#[test]
+fn score_by_placement() {
+    // the attack counts less the earlier the player is knocked out
+    let scores: Vec<_> = (1..=4)
+        .map(|placement| {
+            let mut score = Score::new();
+            score.update(100, 1000, placement, 4);
+            score
+        })
+        .collect();
+    assert!(scores.windows(2).all(|pair| pair[0] > pair[1]));
+
+    // a 1v1 loss is half an attack
+    let mut win = Score::new();
+    win.update(50, 1000, 1, 2);
+    let mut loss = Score::new();
+    loss.update(100, 1000, 2, 2);
+    assert_eq!(win.cmp(&loss), std::cmp::Ordering::Equal);
+}
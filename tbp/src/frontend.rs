@@ -0,0 +1,215 @@
+use crate::*;
+use bot::Bot;
+use std::collections::VecDeque;
+use tetris::{
+    Board, FieldPiece, LastAction, Piece, PieceState, PlacementKind, RotationState, State,
+};
+
+// keeps the game of the frontend and answers its messages
+pub struct Frontend {
+    bot: Bot,
+    think_time: u32, // ms per suggestion
+    state: Option<State>,
+}
+
+impl Frontend {
+    pub fn new(bot: Bot, think_time: u32) -> Self {
+        Frontend {
+            bot,
+            think_time,
+            state: None,
+        }
+    }
+
+    pub fn info() -> BotMessage {
+        BotMessage::Info {
+            name: "Artemis".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            author: "n4mlz".to_string(),
+            features: vec![],
+        }
+    }
+
+    pub fn state(&self) -> Option<&State> {
+        self.state.as_ref()
+    }
+
+    // returns the message to send back, if any
+    pub fn handle(&mut self, message: FrontendMessage) -> Option<BotMessage> {
+        match message {
+            FrontendMessage::Rules => Some(BotMessage::Ready),
+            FrontendMessage::Start(start) => {
+                self.state = Some(start_state(start));
+                None
+            }
+            FrontendMessage::Suggest => {
+                // no moves before the start, which the frontend takes as giving up
+                let moves = self
+                    .state
+                    .clone()
+                    .and_then(|state| self.bot.get_move_for_time(state, self.think_time))
+                    .and_then(|next_state| next_state.last_action)
+                    .map(|last_action| vec![to_move(&last_action)])
+                    .unwrap_or_default();
+                Some(BotMessage::Suggestion { moves })
+            }
+            FrontendMessage::Play { mv } => {
+                let state = self.state.take()?;
+                let ruleset = &self.bot.ruleset;
+                // a move that the bot cannot find is placed as it is, without a spin
+                let new_state = play(&state, mv, ruleset).or_else(|| {
+                    eprintln!("artemis: unknown move {:?}", mv);
+                    state.place_at(from_location(mv.location, ruleset), ruleset)
+                });
+                // NOTE: the game goes out of sync if the piece cannot be placed there
+                self.state = Some(new_state.unwrap_or_else(|| {
+                    eprintln!("artemis: impossible move {:?}", mv);
+                    state
+                }));
+                None
+            }
+            FrontendMessage::NewPiece { piece } => {
+                let state = self.state.as_mut()?;
+                state.push_next_piece(piece);
+                if state.current_piece.is_none() {
+                    state.current_piece = state.next_pieces.pop_front();
+                }
+                None
+            }
+            FrontendMessage::Stop => {
+                self.state = None;
+                None
+            }
+            FrontendMessage::Quit | FrontendMessage::Unknown => None,
+        }
+    }
+}
+
+fn start_state(start: Start) -> State {
+    let mut cells = [0; 40];
+    for (y, row) in start.board.iter().enumerate().take(40) {
+        for (x, cell) in row.iter().enumerate().take(10) {
+            if cell.is_some() {
+                cells[y] |= tetris::row_x(x as i32);
+            }
+        }
+    }
+
+    let mut queue = VecDeque::from(start.queue);
+    let current_piece = queue.pop_front();
+
+    let mut state = State {
+        board: Board::from_cells(cells),
+        current_piece,
+        hold_piece: start.hold,
+        next_pieces: VecDeque::new(),
+        b2b_chain: start.back_to_back as u32,
+        bag: 0,
+        last_action: None,
+        garbage_queue: VecDeque::new(),
+    };
+
+    for piece in queue {
+        state.push_next_piece(piece);
+    }
+
+    if let Some(RandomizerState::SevenBag { bag_state }) = start.randomizer {
+        state.bag = bag_state.iter().fold(0, |bag, piece| bag | piece.bit());
+    }
+
+    // the combo is carried by the last action
+    if start.combo > 0 {
+        let piece = current_piece.unwrap_or(Piece::I);
+        state.last_action = Some(LastAction {
+            placement_kind: PlacementKind::None,
+            placed_piece: piece,
+            field_piece: FieldPiece::new_from_piece(piece, Default::default()),
+            b2b: false,
+            b2b_chain: state.b2b_chain,
+            combo: start.combo,
+            perfect_clear: false,
+            garbage_sent: 0,
+            garbage_cancelled: 0,
+            time: 0,
+            movements_history: vec![],
+        });
+    }
+
+    state
+}
+
+fn play(state: &State, mv: Move, ruleset: &tetris::Ruleset) -> Option<State> {
+    let mut cells = from_location(mv.location, ruleset).cells();
+    cells.sort();
+
+    let candidates: Vec<_> = state
+        .legal_actions(ruleset)
+        .into_iter()
+        .filter(|next_state| {
+            let last_action = next_state.last_action.as_ref().unwrap();
+            let mut placed_cells = last_action.field_piece.cells();
+            placed_cells.sort();
+            last_action.placed_piece == mv.location.piece && placed_cells == cells
+        })
+        .collect();
+
+    // the same cells can be reached in another orientation (I, S and Z), or with and without a spin
+    let to_move = |next_state: &&State| to_move(next_state.last_action.as_ref().unwrap());
+    candidates
+        .iter()
+        .find(|next_state| to_move(next_state) == mv)
+        .or_else(|| {
+            candidates
+                .iter()
+                .find(|next_state| to_move(next_state).spin == mv.spin)
+        })
+        .or(candidates.first())
+        .cloned()
+}
+
+pub fn to_move(last_action: &LastAction) -> Move {
+    use PlacementKind::*;
+
+    let field_piece = last_action.field_piece;
+    let (x, y) = field_piece.srs_center();
+    let orientation = match field_piece.piece_state.rotation {
+        RotationState::North => Orientation::North,
+        RotationState::East => Orientation::East,
+        RotationState::South => Orientation::South,
+        RotationState::West => Orientation::West,
+    };
+    let spin = match last_action.placement_kind {
+        MiniTspin | MiniTspin1 | MiniTspin2 | MiniSpin { .. } => crate::Spin::Mini,
+        Tspin | Tspin1 | Tspin2 | Tspin3 | Spin { .. } => crate::Spin::Full,
+        _ => crate::Spin::None,
+    };
+
+    Move {
+        location: PieceLocation {
+            piece: last_action.placed_piece,
+            orientation,
+            x,
+            y,
+        },
+        spin,
+    }
+}
+
+pub fn from_location(location: PieceLocation, ruleset: &tetris::Ruleset) -> FieldPiece {
+    let rotation = match location.orientation {
+        Orientation::North => RotationState::North,
+        Orientation::East => RotationState::East,
+        Orientation::South => RotationState::South,
+        Orientation::West => RotationState::West,
+    };
+    let piece_state = PieceState {
+        piece: location.piece,
+        rotation,
+    };
+
+    FieldPiece::from_srs_center(
+        piece_state,
+        (location.x, location.y),
+        ruleset.rotation_system,
+    )
+}
@@ -0,0 +1,5 @@
+mod frontend;
+mod message;
+
+pub use frontend::*;
+pub use message::*;
@@ -0,0 +1,56 @@
+use std::io::{BufRead, Write};
+use tbp::*;
+
+// the default time to think per suggestion
+const THINK_TIME: u32 = 200;
+
+fn main() {
+    // usage: tbp [guideline | tetrio | jstris | ppt | tetris99] [think time in ms]
+    let mut args = std::env::args().skip(1);
+    let ruleset = args
+        .next()
+        .and_then(|name| tetris::Ruleset::from_preset(&name))
+        .unwrap_or_default();
+    let think_time = args
+        .next()
+        .and_then(|time| time.parse().ok())
+        .unwrap_or(THINK_TIME);
+
+    let mut frontend = Frontend::new(
+        bot::Bot::new(bot::Evaluator::default(), ruleset),
+        think_time,
+    );
+
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+
+    send(&mut stdout, &Frontend::info());
+
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+
+        let message = match serde_json::from_str::<FrontendMessage>(&line) {
+            Ok(message) => message,
+            Err(err) => {
+                eprintln!("artemis: invalid message: {}", err);
+                continue;
+            }
+        };
+
+        if message == FrontendMessage::Quit {
+            break;
+        }
+
+        if let Some(reply) = frontend.handle(message) {
+            send(&mut stdout, &reply);
+        }
+    }
+}
+
+fn send(stdout: &mut std::io::Stdout, message: &BotMessage) {
+    let json = serde_json::to_string(message).unwrap();
+    writeln!(stdout, "{}", json).unwrap();
+    stdout.flush().unwrap();
+}
@@ -0,0 +1,133 @@
+use tbp::*;
+
+const THINK_TIME: u32 = 100;
+
+fn message(json: &str) -> FrontendMessage {
+    serde_json::from_str(json).unwrap()
+}
+
+#[test]
+fn play_a_suggestion() {
+    let bot = bot::Bot::new(bot::Evaluator::default(), tetris::Ruleset::default());
+    let mut frontend = Frontend::new(bot, THINK_TIME);
+
+    let reply = frontend.handle(message(r#"{ "type": "rules", "randomizer": "seven_bag" }"#));
+    assert_eq!(reply, Some(BotMessage::Ready));
+    assert_eq!(
+        message(r#"{ "type": "something_new" }"#),
+        FrontendMessage::Unknown
+    );
+
+    let mut board = vec![vec![None::<String>; 10]; 40];
+    board[0] = vec![Some("G".to_string()); 9];
+    board[0].push(None);
+    let start = serde_json::json!({
+        "type": "start",
+        "hold": null,
+        "queue": ["T", "I", "O", "S", "Z"],
+        "combo": 0,
+        "back_to_back": false,
+        "board": board,
+    });
+    assert_eq!(frontend.handle(message(&start.to_string())), None);
+    assert_eq!(frontend.state().unwrap().board.collumn_heights[0], 1);
+
+    let Some(BotMessage::Suggestion { moves }) = frontend.handle(FrontendMessage::Suggest) else {
+        panic!();
+    };
+    let mv = moves[0];
+
+    // the message format of the spec
+    let json = serde_json::to_value(mv).unwrap();
+    assert!(json["location"]["type"].is_string());
+    assert!(json["location"]["orientation"].is_string());
+
+    frontend.handle(FrontendMessage::Play { mv });
+    frontend.handle(message(r#"{ "type": "new_piece", "piece": "L" }"#));
+
+    let state = frontend.state().unwrap();
+    assert_eq!(state.last_action.as_ref().map(to_move), Some(mv));
+    assert_eq!(state.next_pieces.back(), Some(&tetris::Piece::L));
+}
+
+#[test]
+fn piece_location() {
+    // the I piece lying flat on the floor at the left wall
+    let location = PieceLocation {
+        piece: tetris::Piece::I,
+        orientation: Orientation::North,
+        x: 1,
+        y: 0,
+    };
+    let mut cells = from_location(location, &tetris::Ruleset::default()).cells();
+    cells.sort();
+    assert_eq!(cells, [(0, 0), (1, 0), (2, 0), (3, 0)]);
+
+    // the O piece in the bottom left corner, in every orientation
+    for (orientation, x, y) in [
+        (Orientation::North, 0, 0),
+        (Orientation::East, 0, 1),
+        (Orientation::South, 1, 1),
+        (Orientation::West, 1, 0),
+    ] {
+        let location = PieceLocation {
+            piece: tetris::Piece::O,
+            orientation,
+            x,
+            y,
+        };
+        let mut cells = from_location(location, &tetris::Ruleset::default()).cells();
+        cells.sort();
+        assert_eq!(cells, [(0, 0), (0, 1), (1, 0), (1, 1)]);
+    }
+}
+
+#[test]
+fn out_of_sync_frontend() {
+    let bot = bot::Bot::new(bot::Evaluator::default(), tetris::Ruleset::default());
+    let mut frontend = Frontend::new(bot, THINK_TIME);
+
+    // a suggestion is always answered, even before the start
+    assert_eq!(
+        frontend.handle(FrontendMessage::Suggest),
+        Some(BotMessage::Suggestion { moves: vec![] })
+    );
+
+    let start = serde_json::json!({
+        "type": "start",
+        "hold": null,
+        "queue": ["T", "I", "O", "S", "Z"],
+        "combo": 0,
+        "back_to_back": false,
+        "board": vec![vec![None::<String>; 10]; 40],
+    });
+    frontend.handle(message(&start.to_string()));
+
+    // a floating I piece after a hold, which no path of the bot reaches
+    let mv = Move {
+        location: PieceLocation {
+            piece: tetris::Piece::I,
+            orientation: Orientation::North,
+            x: 4,
+            y: 5,
+        },
+        spin: Spin::None,
+    };
+    frontend.handle(FrontendMessage::Play { mv });
+    let state = frontend.state().unwrap();
+    assert_eq!(state.board.cells[5], 0b0001111000);
+    assert_eq!(state.hold_piece, Some(tetris::Piece::T));
+    assert_eq!(state.current_piece, Some(tetris::Piece::O));
+
+    // a piece that is not in the queue keeps the game as it was
+    let state = state.clone();
+    let mv = Move {
+        location: PieceLocation {
+            piece: tetris::Piece::L,
+            ..mv.location
+        },
+        spin: Spin::None,
+    };
+    frontend.handle(FrontendMessage::Play { mv });
+    assert_eq!(frontend.state(), Some(&state));
+}
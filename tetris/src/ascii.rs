@@ -0,0 +1,177 @@
+use crate::*;
+use std::collections::VecDeque;
+
+// text notation for tests, the top row first:
+//
+//   current: T
+//   hold: -
+//   next: IOS
+//   b2b: 0
+//   ...@@@....
+//   ##..@#####
+//
+// '#', 'G' and piece letters are filled cells, '.' is an empty cell, and '@' is the active piece
+const ACTIVE_PIECE: char = '@';
+
+fn is_filled(c: char) -> Option<bool> {
+    match c {
+        '.' | '_' | ACTIVE_PIECE => Some(false),
+        '#' | 'G' | 'X' => Some(true),
+        _ => c.to_string().parse::<Piece>().ok().map(|_| true),
+    }
+}
+
+fn is_board_row(line: &str) -> bool {
+    line.chars().count() == 10 && line.chars().all(|c| is_filled(c).is_some())
+}
+
+// cells of each board row from the bottom
+fn board_rows(ascii: &str) -> Vec<&str> {
+    ascii
+        .lines()
+        .map(str::trim)
+        .filter(|line| is_board_row(line))
+        .rev()
+        .collect()
+}
+
+impl Board {
+    // returns None if a row is not 10 cells wide
+    pub fn from_ascii(ascii: &str) -> Option<Board> {
+        let lines: Vec<_> = ascii
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        if lines.len() > 40 || !lines.iter().all(|line| is_board_row(line)) {
+            return None;
+        }
+
+        Some(board_from_rows(&board_rows(ascii)))
+    }
+
+    pub fn to_ascii(&self) -> String {
+        let height = self.collumn_heights.iter().max().copied().unwrap_or(0) as i32;
+        (0..height.max(1))
+            .rev()
+            .map(|y| {
+                (0..10)
+                    .map(|x| if self.occupied(x, y) { '#' } else { '.' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn board_from_rows(rows: &[&str]) -> Board {
+    let mut cells = [0; 40];
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            if is_filled(c) == Some(true) {
+                cells[y] |= row_x(x as i32);
+            }
+        }
+    }
+    Board::from_cells(cells)
+}
+
+impl FieldPiece {
+    // the piece drawn with '@' in the board notation
+    pub fn from_ascii(
+        ascii: &str,
+        piece: Piece,
+        rotation_system: RotationSystemKind,
+    ) -> Option<FieldPiece> {
+        let mut cells = vec![];
+        for (y, row) in board_rows(ascii).iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == ACTIVE_PIECE {
+                    cells.push((x as i32, y as i32));
+                }
+            }
+        }
+        cells.sort();
+
+        // try every rotation with every cell of the shape on the first '@'
+        let (x, y) = *cells.first()?;
+        use RotationState::*;
+        [North, East, South, West].into_iter().find_map(|rotation| {
+            let piece_state = PieceState { piece, rotation };
+            rotation_system
+                .cells(piece_state)
+                .iter()
+                .find_map(|&(dx, dy)| {
+                    let field_piece = FieldPiece {
+                        piece_state,
+                        position: (x - dx, y - dy),
+                        ..FieldPiece::new_from_piece(piece, rotation_system)
+                    };
+                    let mut piece_cells = field_piece.cells();
+                    piece_cells.sort();
+                    Some(field_piece).filter(|_| piece_cells[..] == cells[..])
+                })
+        })
+    }
+}
+
+impl State {
+    // returns None if a header or a row cannot be read
+    pub fn from_ascii(ascii: &str) -> Option<State> {
+        let mut state = State {
+            board: board_from_rows(&board_rows(ascii)),
+            current_piece: None,
+            hold_piece: None,
+            next_pieces: VecDeque::new(),
+            b2b_chain: 0,
+            bag: 0,
+            last_action: None,
+            garbage_queue: VecDeque::new(),
+        };
+
+        let parse_piece = |value: &str| match value {
+            "-" | "" => Some(None),
+            _ => value.parse::<Piece>().ok().map(Some),
+        };
+
+        for line in ascii.lines().map(str::trim) {
+            if line.is_empty() || is_board_row(line) {
+                continue;
+            }
+
+            let (key, value) = line.split_once(':')?;
+            let value = value.trim();
+            match key.trim() {
+                "current" => state.current_piece = parse_piece(value)?,
+                "hold" => state.hold_piece = parse_piece(value)?,
+                "next" => {
+                    for c in value.chars().filter(|c| !c.is_whitespace()) {
+                        state.push_next_piece(c.to_string().parse().ok()?);
+                    }
+                }
+                "b2b" => state.b2b_chain = value.parse().ok()?,
+                _ => return None,
+            }
+        }
+
+        Some(state)
+    }
+
+    pub fn to_ascii(&self) -> String {
+        let piece = |piece: Option<Piece>| piece.map_or("-".to_string(), |p| format!("{:?}", p));
+        let next: String = self
+            .next_pieces
+            .iter()
+            .map(|p| format!("{:?}", p))
+            .collect();
+
+        format!(
+            "current: {}\nhold: {}\nnext: {}\nb2b: {}\n{}",
+            piece(self.current_piece),
+            piece(self.hold_piece),
+            next,
+            self.b2b_chain,
+            self.board.to_ascii()
+        )
+    }
+}
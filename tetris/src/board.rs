@@ -1,8 +1,8 @@
 use crate::*;
-use rand::{seq::IteratorRandom, thread_rng, Rng};
+use rand::Rng;
 use std::{collections::VecDeque, hash::Hash};
 
-pub type Time = u32;
+pub type Time = u32; // in frames (60 per second)
 
 #[derive(Debug, Clone)]
 pub struct MovementState {
@@ -14,14 +14,103 @@ pub struct MovementState {
     pub time: Time,
 }
 
+// the move resets used since the piece reached its lowest row
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct LockDelay {
+    pub resets: u32,
+    pub lowest_y: i32,
+}
+
+impl LockDelay {
+    pub fn new(field_piece: FieldPiece) -> LockDelay {
+        LockDelay {
+            resets: 0,
+            lowest_y: field_piece.position.1,
+        }
+    }
+
+    // grounded: whether the piece was on the ground when the movement started
+    // returns None if the piece locks before the movement ends
+    pub fn after(
+        &self,
+        movement: PieceMovement,
+        cells: u32,
+        grounded: bool,
+        new_y: i32,
+        move_reset: &MoveReset,
+    ) -> Option<LockDelay> {
+        // the count starts over when the piece falls lower than ever
+        if new_y < self.lowest_y {
+            return Some(LockDelay {
+                resets: 0,
+                lowest_y: new_y,
+            });
+        }
+
+        use PieceMovement::*;
+        let is_reset = match movement {
+            MoveLeft | MoveRight | DasLeft | DasRight => true,
+            RotateLeft | RotateRight | Rotate180 => !move_reset.infinite_spin,
+            SoftDrop | HardDrop | Hold => false,
+        };
+        if !grounded || !is_reset {
+            return Some(*self);
+        }
+
+        // each cell of a das is a move
+        let resets = self.resets + cells;
+        match move_reset.limit {
+            Some(limit) if resets > limit => None,
+            _ => Some(LockDelay { resets, ..*self }),
+        }
+    }
+}
+
+// gravity: the piece falls a cell for each whole cell of progress, which is carried over in the air
+// and lost on the ground (the kick of the last rotation is forgotten, as with a move)
+pub(crate) fn fall(
+    mut field_piece: FieldPiece,
+    mut progress: f64,
+    fits: impl Fn(FieldPiece) -> bool,
+) -> (FieldPiece, f64) {
+    while progress >= 1.0 && fits(field_piece.move_by(0, -1)) {
+        field_piece = field_piece.move_by(0, -1);
+        progress -= 1.0;
+    }
+    if !fits(field_piece.move_by(0, -1)) {
+        progress = 0.0;
+    }
+    (field_piece, progress)
+}
+
+// das to the wall, where the piece falls into holes on the way in 20G
+pub(crate) fn slide(
+    mut field_piece: FieldPiece,
+    dx: i32,
+    is_20g: bool,
+    fits: impl Fn(FieldPiece) -> bool,
+) -> (FieldPiece, u32) {
+    let mut count = 0;
+    while fits(field_piece.move_by(dx, 0)) {
+        field_piece = field_piece.move_by(dx, 0);
+        count += 1;
+        if is_20g {
+            field_piece = fall(field_piece, f64::INFINITY, &fits).0;
+        }
+    }
+    (field_piece, count)
+}
+
 impl MovementState {
     pub fn new_from_piece(
         current_piece: Piece,
         hold_piece: Option<Piece>,
         next_pieces: VecDeque<Piece>,
+        rotation_system: RotationSystemKind,
     ) -> MovementState {
+        let field_piece = FieldPiece::new_from_piece(current_piece, rotation_system);
         MovementState {
-            field_piece: FieldPiece::new_from_piece(current_piece),
+            field_piece,
             hold_piece,
             next_pieces,
             movements_history: vec![],
@@ -30,86 +119,26 @@ impl MovementState {
         }
     }
 
-    fn next_movement_state(
-        &self,
-        field_piece: FieldPiece,
-        piece_movement: PieceMovement,
-        time: Time,
-    ) -> MovementState {
-        let mut new_movements_history = self.movements_history.clone();
-        new_movements_history.push(piece_movement);
-
-        MovementState {
-            field_piece,
-            hold_piece: self.hold_piece,
-            next_pieces: self.next_pieces.clone(),
-            movements_history: new_movements_history,
-            has_held: self.has_held,
-            time: self.time + time,
-        }
-    }
-
-    fn next_movement_state_with_movements(
-        &self,
-        field_piece: FieldPiece,
-        movements: Vec<PieceMovement>,
-        time: Time,
-    ) -> MovementState {
-        let mut new_movements_history = self.movements_history.clone();
-        new_movements_history.extend(movements);
-
-        MovementState {
-            field_piece,
-            hold_piece: self.hold_piece,
-            next_pieces: self.next_pieces.clone(),
-            movements_history: new_movements_history,
-            has_held: self.has_held,
-            time: self.time + time,
-        }
-    }
-
-    pub fn hold(&self) -> Option<MovementState> {
+    pub fn hold(&self, ruleset: &Ruleset) -> Option<MovementState> {
         if self.has_held || !self.movements_history.is_empty() {
             return None;
         }
 
-        match self.hold_piece {
-            Some(hold_piece) => Some(MovementState {
-                field_piece: FieldPiece::new_from_piece(hold_piece),
-                hold_piece: Some(self.field_piece.piece_state.piece),
-                next_pieces: self.next_pieces.clone(),
-                movements_history: vec![PieceMovement::Hold],
-                has_held: true,
-                time: DEFAULT_ACTION_TIME.hold,
-            }),
-
-            None => {
-                if self.next_pieces.is_empty() {
-                    return None;
-                }
-                let mut new_next_pieces = self.next_pieces.clone();
-                Some(MovementState {
-                    field_piece: FieldPiece::new_from_piece(new_next_pieces.pop_front().unwrap()),
-                    hold_piece: Some(self.field_piece.piece_state.piece),
-                    next_pieces: new_next_pieces,
-                    movements_history: vec![PieceMovement::Hold],
-                    has_held: true,
-                    time: DEFAULT_ACTION_TIME.hold,
-                })
-            }
-        }
-    }
-}
-
-impl Ord for MovementState {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.time.cmp(&other.time)
-    }
-}
+        let mut next_pieces = self.next_pieces.clone();
+        let piece = match self.hold_piece {
+            Some(hold_piece) => hold_piece,
+            None => next_pieces.pop_front()?,
+        };
+        let field_piece = FieldPiece::new_from_piece(piece, self.field_piece.rotation_system);
 
-impl PartialOrd for MovementState {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+        Some(MovementState {
+            field_piece,
+            hold_piece: Some(self.field_piece.piece_state.piece),
+            next_pieces,
+            movements_history: vec![PieceMovement::Hold],
+            has_held: true,
+            time: ruleset.action_time.hold,
+        })
     }
 }
 
@@ -138,11 +167,30 @@ pub fn row_x(x: i32) -> u16 {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Board {
+    #[cfg_attr(feature = "serde", serde(with = "serde_cells"))]
     pub cells: [u16; 40],
     pub collumn_heights: [u32; 10],
 }
 
+// serde only supports arrays of up to 32 elements
+#[cfg(feature = "serde")]
+mod serde_cells {
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(cells: &[u16; 40], serializer: S) -> Result<S::Ok, S::Error> {
+        cells.as_slice().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u16; 40], D::Error> {
+        let cells = Vec::<u16>::deserialize(deserializer)?;
+        cells
+            .try_into()
+            .map_err(|_| D::Error::custom("a board has 40 rows"))
+    }
+}
+
 impl Default for Board {
     fn default() -> Self {
         Self::new()
@@ -157,6 +205,15 @@ impl Board {
         }
     }
 
+    pub fn from_cells(cells: [u16; 40]) -> Board {
+        let mut board = Board {
+            cells,
+            collumn_heights: [0; 10],
+        };
+        board.collumn_heights = board.calc_collumn_heights();
+        board
+    }
+
     pub fn occupied(&self, x: i32, y: i32) -> bool {
         !(0..10).contains(&x) || !(0..40).contains(&y) || (self.cells[y as usize] & row_x(x) > 0)
     }
@@ -185,205 +242,16 @@ impl Board {
             .all(|&(x, y)| !self.occupied(x, y))
     }
 
-    pub fn legal_moves(&self, movement_state: MovementState) -> Vec<MovementState> {
-        if movement_state.field_piece.is_locked {
-            return vec![];
-        }
-
-        let mut result = vec![];
-
-        // TODO: limit the number of lateral moves and rotations at the same height (game rules)
-        for piece_movement in [
-            PieceMovement::MoveLeft,
-            PieceMovement::MoveRight,
-            PieceMovement::SoftDrop,
-            PieceMovement::HardDrop,
-            PieceMovement::RotateLeft,
-            PieceMovement::RotateRight,
-        ] {
-            match piece_movement {
-                PieceMovement::MoveLeft => {
-                    let new_field_piece = movement_state.field_piece.move_by(-1, 0);
-                    if self.attempt(new_field_piece) {
-                        result.push(movement_state.next_movement_state(
-                            new_field_piece,
-                            piece_movement,
-                            DEFAULT_ACTION_TIME.move_one,
-                        ));
-                    }
-                }
-
-                PieceMovement::MoveRight => {
-                    let new_field_piece = movement_state.field_piece.move_by(1, 0);
-                    if self.attempt(new_field_piece) {
-                        result.push(movement_state.next_movement_state(
-                            new_field_piece,
-                            piece_movement,
-                            DEFAULT_ACTION_TIME.move_one,
-                        ));
-                    }
-                }
-
-                // MEMO: not currently supported for soft drop of only one cell
-                PieceMovement::SoftDrop => {
-                    let mut new_field_piece = movement_state.field_piece;
-                    let mut count = 0;
-                    while self.attempt(new_field_piece.move_by(0, -1)) {
-                        new_field_piece = new_field_piece.move_by(0, -1);
-                        count += 1;
-                    }
-                    if count > 0 {
-                        result.push(movement_state.next_movement_state_with_movements(
-                            new_field_piece,
-                            vec![piece_movement; count as usize],
-                            DEFAULT_ACTION_TIME.move_one * count,
-                        ));
-                    }
-                }
-
-                PieceMovement::HardDrop => {
-                    let mut new_field_piece = movement_state.field_piece;
-                    while self.attempt(new_field_piece.move_by(0, -1)) {
-                        new_field_piece = new_field_piece.move_by(0, -1);
-                    }
-                    let mut new_movement_state = movement_state.next_movement_state(
-                        new_field_piece,
-                        piece_movement,
-                        DEFAULT_ACTION_TIME.hard_drop,
-                    );
-                    new_movement_state.field_piece.is_locked = true;
-                    result.push(new_movement_state);
-                }
-
-                // check if the SRS works correctly
-                PieceMovement::RotateLeft => {
-                    // check if the following kick table is correct
-                    const KICK_TABLE_NORMAL: [[(i32, i32); 5]; 4] = [
-                        [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
-                        [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
-                        [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
-                        [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
-                    ];
-
-                    const KICK_TABLE_I: [[(i32, i32); 5]; 4] = [
-                        [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
-                        [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
-                        [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
-                        [(0, 0), (1, 0), (-2, 0), (-2, -1), (1, 2)],
-                    ];
-
-                    let new_field_piece = movement_state.field_piece.rotate_left();
-
-                    match movement_state.field_piece.piece_state.piece {
-                        Piece::O => {
-                            if self.attempt(new_field_piece) {
-                                result.push(movement_state.next_movement_state(
-                                    new_field_piece,
-                                    piece_movement,
-                                    DEFAULT_ACTION_TIME.rotate,
-                                ));
-                            }
-                        }
-                        Piece::I => {
-                            for (srs_state, kick) in KICK_TABLE_I.iter().enumerate() {
-                                let srs_field_piece = new_field_piece.move_by(kick[0].0, kick[0].1);
-                                if self.attempt(srs_field_piece) {
-                                    result.push(movement_state.next_movement_state(
-                                        srs_field_piece.set_super_rotation_state(srs_state as u32),
-                                        piece_movement,
-                                        DEFAULT_ACTION_TIME.rotate,
-                                    ));
-                                    break;
-                                }
-                            }
-                        }
-                        _ => {
-                            for (srs_state, kick) in KICK_TABLE_NORMAL.iter().enumerate() {
-                                let srs_field_piece = new_field_piece.move_by(kick[0].0, kick[0].1);
-                                if self.attempt(srs_field_piece) {
-                                    result.push(movement_state.next_movement_state(
-                                        srs_field_piece.set_super_rotation_state(srs_state as u32),
-                                        piece_movement,
-                                        DEFAULT_ACTION_TIME.rotate,
-                                    ));
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-
-                // check if the SRS works correctly
-                PieceMovement::RotateRight => {
-                    // check if the following kick table is correct
-                    const KICK_TABLE_NORMAL: [[(i32, i32); 5]; 4] = [
-                        [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
-                        [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
-                        [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
-                        [(0, 0), (-2, 0), (-2, -1), (0, 2), (-1, 2)],
-                    ];
-
-                    const KICK_TABLE_I: [[(i32, i32); 5]; 4] = [
-                        [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
-                        [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
-                        [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
-                        [(0, 0), (-2, 0), (1, 0), (1, -2), (-2, 1)],
-                    ];
-
-                    let new_field_piece = movement_state.field_piece.rotate_right();
-
-                    match movement_state.field_piece.piece_state.piece {
-                        Piece::O => {
-                            if self.attempt(new_field_piece) {
-                                result.push(movement_state.next_movement_state(
-                                    new_field_piece,
-                                    piece_movement,
-                                    DEFAULT_ACTION_TIME.rotate,
-                                ));
-                            }
-                        }
-                        Piece::I => {
-                            for (srs_state, kick) in KICK_TABLE_I.iter().enumerate() {
-                                let srs_field_piece = new_field_piece.move_by(kick[0].0, kick[0].1);
-                                if self.attempt(srs_field_piece) {
-                                    result.push(movement_state.next_movement_state(
-                                        srs_field_piece.set_super_rotation_state(srs_state as u32),
-                                        piece_movement,
-                                        DEFAULT_ACTION_TIME.rotate,
-                                    ));
-                                    break;
-                                }
-                            }
-                        }
-                        _ => {
-                            for (srs_state, kick) in KICK_TABLE_NORMAL.iter().enumerate() {
-                                let srs_field_piece = new_field_piece.move_by(kick[0].0, kick[0].1);
-                                if self.attempt(srs_field_piece) {
-                                    result.push(movement_state.next_movement_state(
-                                        srs_field_piece.set_super_rotation_state(srs_state as u32),
-                                        piece_movement,
-                                        DEFAULT_ACTION_TIME.rotate,
-                                    ));
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-
-                // MEMO: holds are not searched here because next must also be considered
-                _ => {}
-            }
-        }
-
-        result
-    }
-
-    pub fn place_piece(&self, movement_state: &MovementState) -> (Board, PlacementKind) {
+    // `last_rotation` is the rotation right before the drop, if any
+    pub fn place_field_piece(
+        &self,
+        field_piece: FieldPiece,
+        last_rotation: Option<PieceMovement>,
+        ruleset: &Ruleset,
+    ) -> (Board, PlacementKind) {
         use PlacementKind::*;
 
         let mut new_board = *self; // copy
-        let field_piece = movement_state.field_piece;
         for &(x, y) in field_piece.cells().iter() {
             new_board.cells[y as usize] |= row_x(x);
         }
@@ -404,13 +272,24 @@ impl Board {
         new_board.collumn_heights = new_board.calc_collumn_heights();
 
         if field_piece.piece_state.piece != Piece::T {
-            let placement_kind = match cleared_rows {
-                0 => None,
-                1 => Clear1,
-                2 => Clear2,
-                3 => Clear3,
-                4 => Clear4,
-                _ => None,
+            let piece = field_piece.piece_state.piece;
+            let lines = cleared_rows as u32;
+
+            // all-spin detection (immobile pieces)
+            let is_spin =
+                last_rotation.is_some() && piece != Piece::O && self.is_immobile(field_piece);
+
+            let placement_kind = match (is_spin, ruleset.spin_rule) {
+                (true, SpinRule::AllMini) => MiniSpin { piece, lines },
+                (true, SpinRule::AllSpin) => Spin { piece, lines },
+                _ => match cleared_rows {
+                    0 => None,
+                    1 => Clear1,
+                    2 => Clear2,
+                    3 => Clear3,
+                    4 => Clear4,
+                    _ => None,
+                },
             };
 
             return (new_board, placement_kind);
@@ -418,22 +297,16 @@ impl Board {
 
         // t-spin detection
         // TODO: check if the following code is correct
-        let movements_history = &movement_state.movements_history;
-        if (movements_history.len() > 1)
-            && matches!(
-                movements_history[movements_history.len() - 2], // get the second to last one since the last one is HardDrop
-                PieceMovement::RotateLeft | PieceMovement::RotateRight
-            )
-        {
+        if let Some(last_rotation) = last_rotation {
             let t_piece_corners = [(-1, 1), (1, 1), (1, -1), (-1, -1)];
             let t_piece_corners = (0..4)
                 .map(|i| {
                     self.occupied(
                         field_piece.position.0
-                            + t_piece_corners[(i + field_piece.piece_state.rotation as usize) / 4]
+                            + t_piece_corners[(i + field_piece.piece_state.rotation as usize) % 4]
                                 .0,
                         field_piece.position.1
-                            + t_piece_corners[(i + field_piece.piece_state.rotation as usize) / 4]
+                            + t_piece_corners[(i + field_piece.piece_state.rotation as usize) % 4]
                                 .1,
                     )
                 })
@@ -441,10 +314,12 @@ impl Board {
 
             // t-spin
             if t_piece_corners.iter().filter(|&&x| x).count() >= 3 {
+                // the last kick of the table always makes a full t-spin (except for 180 rotations)
+                let is_last_kick = last_rotation != PieceMovement::Rotate180
+                    && field_piece.super_rotation_state == Some(4);
+
                 // t-spin mini
-                if (!t_piece_corners[0] || !t_piece_corners[1])
-                    && field_piece.super_rotation_state != Some(3)
-                {
+                if (!t_piece_corners[0] || !t_piece_corners[1]) && !is_last_kick {
                     let placement_kind = match cleared_rows {
                         0 => MiniTspin,
                         1 => MiniTspin1,
@@ -480,33 +355,40 @@ impl Board {
         (new_board, placement_kind)
     }
 
-    pub fn receive_garbage(&self, garbage: u32) -> Board {
-        let mut rng = thread_rng();
-        let mut new_board = *self; // copy
-        let mut hole_positions = vec![rng.gen_range(0..10)];
+    fn is_immobile(&self, field_piece: FieldPiece) -> bool {
+        [(-1, 0), (1, 0), (0, 1), (0, -1)]
+            .iter()
+            .all(|&(dx, dy)| !self.attempt(field_piece.move_by(dx, dy)))
+    }
 
-        for _ in 1..garbage as usize {
-            if rng.gen_range(0.0..1.0) < SAME_HOLE_POSITION_RATE {
-                hole_positions.push(*hole_positions.last().unwrap());
-            } else {
-                hole_positions.push(
-                    (0..10)
-                        .filter(|&x| x != *hole_positions.last().unwrap())
-                        .choose(&mut rng)
-                        .unwrap(),
-                );
-            }
-        }
+    pub fn receive_garbage(&self, garbage: u32, ruleset: &Ruleset, rng: &mut impl Rng) -> Board {
+        Garbage::random(garbage, 0, self.garbage_hole(), false, ruleset, rng)
+            .iter()
+            .fold(*self, |board, garbage| {
+                board.insert_garbage(garbage.amount, garbage.hole)
+            })
+    }
+
+    // the hole of the garbage line at the bottom, if the bottom line looks like one
+    pub fn garbage_hole(&self) -> Option<i32> {
+        let row = self.cells[0];
+        Some(row.trailing_ones() as i32).filter(|_| row.count_ones() == 9)
+    }
+
+    // pushes the board up by `lines` lines with a hole in the column
+    pub fn insert_garbage(&self, lines: u32, hole: i32) -> Board {
+        let mut new_board = *self; // copy
 
         for y in (0..40).rev() {
-            if y >= garbage as usize {
-                new_board.cells[y] = new_board.cells[y - garbage as usize];
+            if y >= lines as usize {
+                new_board.cells[y] = new_board.cells[y - lines as usize];
             } else {
-                new_board.cells[y] = 0x3ff & !row_x(hole_positions.pop().unwrap());
+                new_board.cells[y] = 0x3ff & !row_x(hole);
             }
         }
 
-        new_board.collumn_heights = new_board.collumn_heights.map(|h| h + garbage);
+        // the hole column stays empty if it was, and the top rows can be pushed out
+        new_board.collumn_heights = new_board.calc_collumn_heights();
 
         new_board
     }
@@ -25,6 +25,21 @@ impl fmt::Display for PairState {
     }
 }
 
+// states side by side, for battles of more than two players
+pub struct States(pub Vec<State>);
+
+impl fmt::Display for States {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, state) in self.0.iter().enumerate() {
+            display_state(f, state, Some((48 * i as u16, 0)))?;
+        }
+
+        write!(f, "{}", cursor::Goto(0, 26))?;
+
+        fmt::Result::Ok(())
+    }
+}
+
 pub fn display_state(
     f: &mut fmt::Formatter,
     state: &State,
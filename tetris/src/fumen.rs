@@ -0,0 +1,488 @@
+use crate::*;
+use std::collections::VecDeque;
+
+// fumen v115 (https://github.com/knewjade/tetris-fumen)
+// the field has 23 rows and a garbage row below them, and each cell holds a color (0: empty, 8: gray)
+const FIELD_TOP: usize = 23;
+const FIELD_WIDTH: usize = 10;
+const FIELD_BLOCKS: usize = (FIELD_TOP + 1) * FIELD_WIDTH;
+const PLAY_BLOCKS: usize = FIELD_TOP * FIELD_WIDTH;
+
+const ENCODE_TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_TABLE: &[u8] =
+    b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const COMMENT_CHAR_VALUE: u32 = COMMENT_TABLE.len() as u32 + 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FumenPage {
+    pub board: Board,
+    pub piece: Option<FieldPiece>,
+    pub comment: String,
+    pub lock: bool, // the piece is placed (and lines are cleared) on the board of the next page
+}
+
+impl FumenPage {
+    pub fn new(board: Board, piece: Option<FieldPiece>) -> Self {
+        FumenPage {
+            board,
+            piece,
+            comment: String::new(),
+            lock: true,
+        }
+    }
+
+    // fumen has no queue, so only the active piece is known
+    pub fn state(&self) -> State {
+        State {
+            board: self.board,
+            current_piece: self.piece.map(|piece| piece.piece_state.piece),
+            hold_piece: None,
+            next_pieces: VecDeque::new(),
+            b2b_chain: 0,
+            bag: 0,
+            last_action: None,
+            garbage_queue: VecDeque::new(),
+        }
+    }
+}
+
+// returns None if the string is not a valid v115 fumen
+pub fn decode_fumen(fumen: &str) -> Option<Vec<FumenPage>> {
+    let (version, data) = fumen.trim().split_once('@')?;
+    if !matches!(version, "v115" | "m115" | "d115") {
+        return None;
+    }
+
+    let mut reader = Reader::new(data)?;
+    let mut pages = vec![];
+    let mut field = [0u8; FIELD_BLOCKS];
+    let mut repeat_count = 0;
+    let mut comment = String::new();
+
+    while !reader.is_empty() {
+        if repeat_count > 0 {
+            repeat_count -= 1;
+        } else {
+            let mut index = 0;
+            let mut is_changed = true;
+            while index < FIELD_BLOCKS {
+                let value = reader.poll(2)? as usize;
+                let (diff, count) = (value / FIELD_BLOCKS, value % FIELD_BLOCKS + 1);
+                if diff == 8 && count == FIELD_BLOCKS {
+                    is_changed = false;
+                }
+                for _ in 0..count {
+                    let cell = field.get_mut(index)?;
+                    *cell = (*cell as i32 + diff as i32 - 8).clamp(0, 8) as u8;
+                    index += 1;
+                }
+            }
+            if !is_changed {
+                repeat_count = reader.poll(1)?;
+            }
+        }
+
+        let action = Action::decode(reader.poll(3)?);
+
+        if action.comment {
+            let length = reader.poll(2)? as usize;
+            let mut escaped = String::new();
+            for _ in 0..length.div_ceil(4) {
+                let mut value = reader.poll(5)?;
+                for _ in 0..4 {
+                    let index = (value % COMMENT_CHAR_VALUE) as usize;
+                    escaped.push(*COMMENT_TABLE.get(index)? as char);
+                    value /= COMMENT_CHAR_VALUE;
+                }
+            }
+            escaped.truncate(length);
+            comment = unescape(&escaped);
+        }
+
+        pages.push(FumenPage {
+            board: field_to_board(&field),
+            piece: action.piece,
+            comment: comment.clone(),
+            lock: action.lock,
+        });
+
+        if action.lock {
+            lock_piece(&mut field, action.piece);
+            if action.rise {
+                rise_garbage(&mut field);
+            }
+            if action.mirror {
+                for row in field[..PLAY_BLOCKS].chunks_mut(FIELD_WIDTH) {
+                    row.reverse();
+                }
+            }
+        }
+    }
+
+    Some(pages)
+}
+
+pub fn encode_fumen(pages: &[FumenPage]) -> String {
+    let mut values = vec![];
+    let mut field = [0u8; FIELD_BLOCKS];
+    let mut last_repeat_index: Option<usize> = None;
+    let mut last_comment = String::new();
+
+    for (i, page) in pages.iter().enumerate() {
+        // the cells kept from the previous page keep their colors, and new cells are gray
+        let mut current_field = [0u8; FIELD_BLOCKS];
+        for (index, cell) in current_field[..PLAY_BLOCKS].iter_mut().enumerate() {
+            let (x, y) = field_position(index);
+            if page.board.occupied(x, y) {
+                *cell = if field[index] > 0 { field[index] } else { 8 };
+            }
+        }
+
+        let field_values = encode_field(&field, &current_field);
+        let is_changed = field_values != [(8, FIELD_BLOCKS)];
+
+        match last_repeat_index {
+            // the same field is not written again, but counted
+            Some(index) if !is_changed && values[index] < ENCODE_TABLE.len() as u32 - 1 => {
+                values[index] += 1;
+            }
+            _ => {
+                for (diff, count) in field_values {
+                    push_value(&mut values, (diff * FIELD_BLOCKS + count - 1) as u32, 2);
+                }
+                if is_changed {
+                    last_repeat_index = None;
+                } else {
+                    values.push(0);
+                    last_repeat_index = Some(values.len() - 1);
+                }
+            }
+        }
+
+        // NOTE: a piece out of the field has no location in fumen, so the page is written without it
+        let piece = page.piece.filter(|&piece| piece_location(piece).is_some());
+        let has_comment = page.comment != last_comment;
+        let action = Action {
+            piece,
+            rise: false,
+            mirror: false,
+            colorize: i == 0,
+            comment: has_comment,
+            lock: page.lock,
+        };
+        push_value(&mut values, action.encode(), 3);
+
+        if has_comment {
+            let escaped = escape(&page.comment);
+            let escaped = &escaped[..escaped.len().min(4095)];
+            push_value(&mut values, escaped.len() as u32, 2);
+            for chunk in escaped.as_bytes().chunks(4) {
+                let value = chunk.iter().rev().fold(0, |value, c| {
+                    let index = COMMENT_TABLE.iter().position(|t| t == c).unwrap_or(0);
+                    value * COMMENT_CHAR_VALUE + index as u32
+                });
+                push_value(&mut values, value, 5);
+            }
+            last_comment = page.comment.clone();
+        }
+
+        field = current_field;
+        if page.lock {
+            lock_piece(&mut field, piece);
+        }
+    }
+
+    let mut fumen = "v115@".to_string();
+    for (i, value) in values.iter().enumerate() {
+        // the editor breaks a line every 47 characters (including "v115@")
+        if i >= 42 && (i - 42) % 47 == 0 {
+            fumen.push('?');
+        }
+        fumen.push(ENCODE_TABLE[*value as usize] as char);
+    }
+    fumen
+}
+
+// a page per placement, e.g. a game played by the bot
+// each state must be reached from the previous one by its last action
+pub fn encode_replay(states: &[State]) -> String {
+    let mut pages: Vec<_> = states
+        .windows(2)
+        .map(|states| {
+            let piece = states[1]
+                .last_action
+                .as_ref()
+                .map(|last_action| last_action.field_piece);
+            FumenPage::new(states[0].board, piece)
+        })
+        .collect();
+
+    if let Some(state) = states.last() {
+        pages.push(FumenPage::new(state.board, None));
+    }
+
+    encode_fumen(&pages)
+}
+
+struct Reader {
+    values: Vec<u32>,
+    index: usize,
+}
+
+impl Reader {
+    fn new(data: &str) -> Option<Self> {
+        let values = data
+            .chars()
+            .filter(|&c| c != '?')
+            .map(|c| ENCODE_TABLE.iter().position(|&t| t as char == c))
+            .map(|index| index.map(|index| index as u32))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Reader { values, index: 0 })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.index >= self.values.len()
+    }
+
+    // little endian
+    fn poll(&mut self, length: usize) -> Option<u32> {
+        let values = self.values.get(self.index..self.index + length)?;
+        self.index += length;
+        Some(values.iter().rev().fold(0, |value, v| value * 64 + v))
+    }
+}
+
+fn push_value(values: &mut Vec<u32>, mut value: u32, length: usize) {
+    for _ in 0..length {
+        values.push(value % 64);
+        value /= 64;
+    }
+}
+
+struct Action {
+    piece: Option<FieldPiece>,
+    rise: bool,
+    mirror: bool,
+    colorize: bool,
+    comment: bool,
+    lock: bool,
+}
+
+impl Action {
+    fn decode(mut value: u32) -> Action {
+        let mut poll = |n: u32| {
+            let v = value % n;
+            value /= n;
+            v
+        };
+
+        let piece_type = poll(8);
+        let rotation = match poll(4) {
+            0 => RotationState::South,
+            1 => RotationState::East,
+            2 => RotationState::North,
+            _ => RotationState::West,
+        };
+        let location = poll(FIELD_BLOCKS as u32) as usize;
+        let rise = poll(2) == 1;
+        let mirror = poll(2) == 1;
+        let colorize = poll(2) == 1;
+        let comment = poll(2) == 1;
+        let lock = poll(2) == 0;
+
+        let piece = from_color(piece_type as u8).map(|piece| {
+            let piece_state = PieceState { piece, rotation };
+            let (x, y) = field_position(location);
+            let (dx, dy) = location_offset(piece_state);
+            FieldPiece::from_srs_center(piece_state, (x + dx, y + dy), RotationSystemKind::Srs)
+        });
+
+        Action {
+            piece,
+            rise,
+            mirror,
+            colorize,
+            comment,
+            lock,
+        }
+    }
+
+    fn encode(&self) -> u32 {
+        let (piece_type, rotation, location) = match self.piece {
+            Some(field_piece) => {
+                let piece_state = field_piece.piece_state;
+                let rotation = match piece_state.rotation {
+                    RotationState::South => 0,
+                    RotationState::East => 1,
+                    RotationState::North => 2,
+                    RotationState::West => 3,
+                };
+                let location = piece_location(field_piece).expect("the piece is in the field");
+                (
+                    to_color(piece_state.piece) as u32,
+                    rotation,
+                    location as u32,
+                )
+            }
+            None => (0, 0, 0),
+        };
+
+        let mut value = !self.lock as u32;
+        value = value * 2 + self.comment as u32;
+        value = value * 2 + self.colorize as u32;
+        value = value * 2 + self.mirror as u32;
+        value = value * 2 + self.rise as u32;
+        value = value * FIELD_BLOCKS as u32 + location;
+        value = value * 4 + rotation;
+        value * 8 + piece_type
+    }
+}
+
+// the location written in fumen is off the center for some pieces
+fn location_offset(piece_state: PieceState) -> Position {
+    use RotationState::*;
+    match (piece_state.piece, piece_state.rotation) {
+        (Piece::O, West) => (1, -1),
+        (Piece::O, South) => (1, 0),
+        (Piece::O, North) => (0, -1),
+        (Piece::I, South) => (1, 0),
+        (Piece::I, West) => (0, -1),
+        (Piece::S, North) => (0, -1),
+        (Piece::S, East) => (-1, 0),
+        (Piece::Z, North) => (0, -1),
+        (Piece::Z, West) => (1, 0),
+        _ => (0, 0),
+    }
+}
+
+fn piece_location(field_piece: FieldPiece) -> Option<usize> {
+    let (x, y) = field_piece.srs_center();
+    let (dx, dy) = location_offset(field_piece.piece_state);
+    field_index(x - dx, y - dy)
+}
+
+fn to_color(piece: Piece) -> u8 {
+    match piece {
+        Piece::I => 1,
+        Piece::L => 2,
+        Piece::O => 3,
+        Piece::Z => 4,
+        Piece::T => 5,
+        Piece::J => 6,
+        Piece::S => 7,
+    }
+}
+
+fn from_color(color: u8) -> Option<Piece> {
+    match color {
+        1 => Some(Piece::I),
+        2 => Some(Piece::L),
+        3 => Some(Piece::O),
+        4 => Some(Piece::Z),
+        5 => Some(Piece::T),
+        6 => Some(Piece::J),
+        7 => Some(Piece::S),
+        _ => None,
+    }
+}
+
+// the index 0 is the top left, and the last row is the garbage row (y = -1)
+fn field_position(index: usize) -> Position {
+    let x = (index % FIELD_WIDTH) as i32;
+    let y = FIELD_TOP as i32 - 1 - (index / FIELD_WIDTH) as i32;
+    (x, y)
+}
+
+fn field_index(x: i32, y: i32) -> Option<usize> {
+    if !(0..FIELD_WIDTH as i32).contains(&x) || !(-1..FIELD_TOP as i32).contains(&y) {
+        return None;
+    }
+    Some((FIELD_TOP as i32 - 1 - y) as usize * FIELD_WIDTH + x as usize)
+}
+
+fn field_to_board(field: &[u8; FIELD_BLOCKS]) -> Board {
+    let mut cells = [0; 40];
+    for (index, &cell) in field[..PLAY_BLOCKS].iter().enumerate() {
+        if cell > 0 {
+            let (x, y) = field_position(index);
+            cells[y as usize] |= row_x(x);
+        }
+    }
+    Board::from_cells(cells)
+}
+
+// runs of (diff + 8, count)
+fn encode_field(prev: &[u8; FIELD_BLOCKS], current: &[u8; FIELD_BLOCKS]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = vec![];
+    for (p, c) in prev.iter().zip(current.iter()) {
+        let diff = (*c as i32 - *p as i32 + 8) as usize;
+        match runs.last_mut() {
+            Some((last_diff, count)) if *last_diff == diff => *count += 1,
+            _ => runs.push((diff, 1)),
+        }
+    }
+    runs
+}
+
+fn lock_piece(field: &mut [u8; FIELD_BLOCKS], piece: Option<FieldPiece>) {
+    if let Some(piece) = piece {
+        for (x, y) in piece.cells() {
+            if let Some(index) = field_index(x, y).filter(|&index| index < PLAY_BLOCKS) {
+                field[index] = to_color(piece.piece_state.piece);
+            }
+        }
+    }
+
+    // clear the filled rows of the play field from the bottom
+    let rows: Vec<[u8; FIELD_WIDTH]> = field[..PLAY_BLOCKS]
+        .chunks(FIELD_WIDTH)
+        .rev()
+        .filter(|row| row.contains(&0))
+        .map(|row| row.try_into().unwrap())
+        .collect();
+    for (i, row) in field[..PLAY_BLOCKS]
+        .chunks_mut(FIELD_WIDTH)
+        .rev()
+        .enumerate()
+    {
+        row.copy_from_slice(rows.get(i).unwrap_or(&[0; FIELD_WIDTH]));
+    }
+}
+
+fn rise_garbage(field: &mut [u8; FIELD_BLOCKS]) {
+    field.copy_within(FIELD_WIDTH.., 0);
+    field[PLAY_BLOCKS..].fill(0);
+}
+
+// the same as `escape` of javascript, which fumen applies to comments
+fn escape(comment: &str) -> String {
+    let mut escaped = String::new();
+    for unit in comment.encode_utf16() {
+        match char::from_u32(unit as u32) {
+            Some(c) if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) => escaped.push(c),
+            _ if unit < 256 => escaped.push_str(&format!("%{:02X}", unit)),
+            _ => escaped.push_str(&format!("%u{:04X}", unit)),
+        }
+    }
+    escaped
+}
+
+fn unescape(escaped: &str) -> String {
+    let mut units = vec![];
+    let mut chars = escaped.chars().peekable();
+    while let Some(c) = chars.next() {
+        let hex = match (c, chars.peek()) {
+            ('%', Some('u')) => {
+                chars.next();
+                chars.by_ref().take(4).collect::<String>()
+            }
+            ('%', _) => chars.by_ref().take(2).collect::<String>(),
+            _ => {
+                units.push(c as u16);
+                continue;
+            }
+        };
+        units.push(u16::from_str_radix(&hex, 16).unwrap_or(b'?' as u16));
+    }
+    String::from_utf16_lossy(&units)
+}
@@ -0,0 +1,151 @@
+use crate::*;
+use rand::{seq::IteratorRandom, Rng};
+use std::collections::VecDeque;
+
+// garbage lines sent by an opponent, waiting to enter the board
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Garbage {
+    pub amount: u32,
+    pub hole: i32,   // the empty column of the lines
+    pub delay: Time, // until it arrives (0: it enters the board with the next piece)
+}
+
+impl Garbage {
+    // the lines of an attack from the top line, grouped by the hole
+    // last_hole: the hole of the previous attack (None: the first attack)
+    // change_hole: the hole of the previous attack cannot be kept (see `GarbageRule::change_on_cancel`)
+    pub fn random(
+        amount: u32,
+        delay: Time,
+        last_hole: Option<i32>,
+        change_hole: bool,
+        ruleset: &Ruleset,
+        rng: &mut impl Rng,
+    ) -> Vec<Garbage> {
+        let mut garbage: Vec<Garbage> = vec![];
+        for line in 0..amount {
+            let hole = garbage.last().map(|last| last.hole).or(last_hole);
+            let hole = match hole {
+                Some(hole) if line == 0 && change_hole => other_column(hole, rng),
+                Some(hole) => ruleset.garbage.holes.next_hole(hole, line == 0, rng),
+                None => rng.gen_range(0..10),
+            };
+
+            match garbage.last_mut() {
+                Some(last) if last.hole == hole => last.amount += 1,
+                _ => garbage.push(Garbage {
+                    amount: 1,
+                    hole,
+                    delay,
+                }),
+            }
+        }
+        garbage
+    }
+}
+
+fn other_column(hole: i32, rng: &mut impl Rng) -> i32 {
+    (0..10).filter(|&x| x != hole).choose(rng).unwrap()
+}
+
+// where the holes of garbage lines are
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GarbageHoles {
+    // the hole moves to another column at the rate inside an attack and between attacks (tetrio)
+    Messiness { inside: f64, between: f64 },
+    Clean,  // every line in the same column
+    Cheese, // every line in another column than the line above
+    Random, // every line in any column
+}
+
+impl GarbageHoles {
+    // the hole of a line below a line with `hole`
+    // first_line: whether the line is the first of an attack
+    pub fn next_hole(&self, hole: i32, first_line: bool, rng: &mut impl Rng) -> i32 {
+        match *self {
+            GarbageHoles::Messiness { inside, between } => {
+                let rate = if first_line { between } else { inside };
+                if rng.gen_range(0.0..1.0) < rate {
+                    other_column(hole, rng)
+                } else {
+                    hole
+                }
+            }
+            GarbageHoles::Clean => hole,
+            GarbageHoles::Cheese => other_column(hole, rng),
+            GarbageHoles::Random => rng.gen_range(0..10),
+        }
+    }
+}
+
+// how garbage travels and enters the board
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct GarbageRule {
+    pub delay: Time,      // from being sent until it can enter the board
+    pub cap: Option<u32>, // the most lines entering the board per piece (None: all)
+    pub holes: GarbageHoles,
+    pub change_on_cancel: bool, // the next attack has another hole after garbage is cancelled
+}
+
+// a generic rule rather than one of a client: the holes of the engine before garbage rules
+// (the hole is kept at a rate of 0.7 inside an attack), and a third of a second of travel
+impl Default for GarbageRule {
+    fn default() -> Self {
+        GarbageRule {
+            delay: 20,
+            cap: None,
+            holes: GarbageHoles::Messiness {
+                inside: 0.3,
+                between: 1.0,
+            },
+            change_on_cancel: false,
+        }
+    }
+}
+
+// outgoing attack cancels the oldest garbage first, and returns the cancelled lines
+pub(crate) fn cancel_garbage(queue: &mut VecDeque<Garbage>, attack: u32) -> u32 {
+    let mut cancelled = 0;
+    while let Some(garbage) = queue.front_mut() {
+        let lines = garbage.amount.min(attack - cancelled);
+        if lines == 0 {
+            break;
+        }
+        garbage.amount -= lines;
+        cancelled += lines;
+        if garbage.amount == 0 {
+            queue.pop_front();
+        }
+    }
+    cancelled
+}
+
+// the arrived lines enter the board (up to the cap)
+pub(crate) fn receive_arrived_garbage(
+    board: &Board,
+    queue: &mut VecDeque<Garbage>,
+    ruleset: &Ruleset,
+) -> Board {
+    let mut board = *board;
+    let mut cap = ruleset.garbage.cap.unwrap_or(u32::MAX);
+    while let Some(garbage) = queue.front_mut() {
+        let lines = garbage.amount.min(cap);
+        if garbage.delay > 0 || lines == 0 {
+            break;
+        }
+        board = board.insert_garbage(lines, garbage.hole);
+        garbage.amount -= lines;
+        cap -= lines;
+        if garbage.amount == 0 {
+            queue.pop_front();
+        }
+    }
+    board
+}
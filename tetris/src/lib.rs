@@ -1,11 +1,24 @@
+mod ascii;
 mod board;
 mod display;
+mod fumen;
+mod garbage;
 mod piece;
+mod placement;
+mod randomizer;
+mod rng;
+mod rotation;
 mod rule;
 mod state;
 
 pub use board::*;
 pub use display::*;
+pub use fumen::*;
+pub use garbage::*;
 pub use piece::*;
+pub use placement::*;
+pub use randomizer::*;
+pub use rng::*;
+pub use rotation::*;
 pub use rule::*;
 pub use state::*;
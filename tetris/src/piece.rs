@@ -1,9 +1,11 @@
-use strum::EnumIter;
+use crate::*;
+use strum::{EnumIter, EnumString};
 
-type Position = (i32, i32);
+pub type Position = (i32, i32);
 
 // TODO: put in order
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, EnumIter)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, EnumIter, EnumString)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Piece {
     S,
     Z,
@@ -15,23 +17,15 @@ pub enum Piece {
 }
 
 impl Piece {
-    pub fn initial_position(&self) -> Position {
-        // NOTE: this is configured for tetrio
-        // TODO: confirm this is correct
-        match self {
-            Piece::S => (4, 20),
-            Piece::Z => (4, 20),
-            Piece::J => (4, 20),
-            Piece::L => (4, 20),
-            Piece::T => (4, 20),
-            Piece::O => (4, 21),
-            Piece::I => (4, 20),
-        }
+    // for sets of pieces
+    pub fn bit(&self) -> u8 {
+        1 << *self as u8
     }
 }
 
 // TODO: put in order
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RotationState {
     North,
     East,
@@ -59,9 +53,20 @@ impl RotationState {
             West => North,
         }
     }
+
+    pub fn rotate_180(&self) -> RotationState {
+        use RotationState::*;
+        match self {
+            North => South,
+            East => West,
+            South => North,
+            West => East,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PieceState {
     pub piece: Piece,
     pub rotation: RotationState,
@@ -82,6 +87,14 @@ impl PieceState {
         }
     }
 
+    pub fn rotate_180(&self) -> PieceState {
+        PieceState {
+            piece: self.piece,
+            rotation: self.rotation.rotate_180(),
+        }
+    }
+
+    // shapes of the guideline (SRS)
     // TODO: fix clippy warning
     pub fn cells(&self) -> [Position; 4] {
         macro_rules! generate_cells {
@@ -93,6 +106,9 @@ impl PieceState {
                     [$((-$y, $x)),*]         // West
                 ]
             };
+            (@o_piece [$(($x:expr, $y:expr)),*]) => {
+                [[$(($x, $y)),*]; 4]      // the O piece does not move when rotated
+            };
             (@i_piece [$(($x:expr, $y:expr)),*]) => {
                 [
                     [$(($x, $y)),*],              // North
@@ -108,7 +124,7 @@ impl PieceState {
                     generate_cells!(@normal [(-1, 0), (0, 0), (1, 0), (-1, 1)]),    // J
                     generate_cells!(@normal [(-1, 0), (0, 0), (1, 0), (1, 1)]),     // L
                     generate_cells!(@normal [(-1, 0), (0, 0), (1, 0), (0, 1)]),     // T
-                    generate_cells!(@normal [(0, 0), (1, 0), (0, -1), (1, -1)]),    // O
+                    generate_cells!(@o_piece [(0, 0), (1, 0), (0, -1), (1, -1)]),   // O
                     generate_cells!(@i_piece [(-1, 0), (0, 0), (1, 0), (2, 0)])     // I
                 ]
             };
@@ -121,28 +137,31 @@ impl PieceState {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FieldPiece {
     pub piece_state: PieceState,
     pub position: Position,
-    pub super_rotation_state: Option<u32>, // Some: 0, 1, 2, 3
+    pub super_rotation_state: Option<u32>, // Some: index of the kick used (0, 1, 2, 3, 4)
     pub is_locked: bool,
+    pub rotation_system: RotationSystemKind,
 }
 
 impl FieldPiece {
-    pub fn new_from_piece(piece: Piece) -> FieldPiece {
+    pub fn new_from_piece(piece: Piece, rotation_system: RotationSystemKind) -> FieldPiece {
         FieldPiece {
             piece_state: PieceState {
                 piece,
                 rotation: RotationState::North,
             },
-            position: piece.initial_position(),
+            position: rotation_system.initial_position(piece),
             super_rotation_state: None,
             is_locked: false,
+            rotation_system,
         }
     }
 
     pub fn cells(&self) -> [Position; 4] {
-        let cells = self.piece_state.cells();
+        let cells = self.rotation_system.cells(self.piece_state);
         cells.map(|(x, y)| (x + self.position.0, y + self.position.1))
     }
 
@@ -170,21 +189,68 @@ impl FieldPiece {
         }
     }
 
+    pub fn rotate_180(&self) -> FieldPiece {
+        FieldPiece {
+            piece_state: self.piece_state.rotate_180(),
+            super_rotation_state: None,
+            ..*self
+        }
+    }
+
     pub fn set_super_rotation_state(&self, state: u32) -> FieldPiece {
         FieldPiece {
             super_rotation_state: Some(state),
             ..*self
         }
     }
+
+    // the center used by fumen and tbp: the cell that SRS rotates around
+    // the I and O pieces have no such cell, so the cell the true center turns around is used
+    pub fn srs_center(&self) -> Position {
+        let (dx, dy) = srs_center_offset(self.piece_state);
+        (self.position.0 + dx, self.position.1 + dy)
+    }
+
+    pub fn from_srs_center(
+        piece_state: PieceState,
+        center: Position,
+        rotation_system: RotationSystemKind,
+    ) -> FieldPiece {
+        let (dx, dy) = srs_center_offset(piece_state);
+        FieldPiece {
+            piece_state,
+            position: (center.0 - dx, center.1 - dy),
+            ..FieldPiece::new_from_piece(piece_state.piece, rotation_system)
+        }
+    }
+}
+
+fn srs_center_offset(piece_state: PieceState) -> Position {
+    use RotationState::*;
+    match (piece_state.piece, piece_state.rotation) {
+        (Piece::I, North) => (0, 0),
+        (Piece::I, East) => (1, 0),
+        (Piece::I, South) => (1, -1),
+        (Piece::I, West) => (0, -1),
+        (Piece::O, North) => (0, -1),
+        (Piece::O, East) => (0, 0),
+        (Piece::O, South) => (1, 0),
+        (Piece::O, West) => (1, -1),
+        _ => (0, 0),
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PieceMovement {
     MoveLeft,
     MoveRight,
+    DasLeft,  // to the wall
+    DasRight, // to the wall
     SoftDrop,
     HardDrop,
     RotateLeft,  // Counter-clockwise
     RotateRight, // Clockwise
+    Rotate180,
     Hold,
 }
@@ -0,0 +1,498 @@
+use crate::*;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+// a place the piece can lock at, found without keeping the inputs (see `State::movements`)
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Placement {
+    pub field_piece: FieldPiece, // locked
+    pub hold: bool,
+    pub last_rotation: Option<PieceMovement>, // the rotation right before the drop (for spins)
+    pub time: Time,                           // the fastest inputs
+}
+
+// bit (x + X_OFFSET) of a row is set when the piece at (x, y) overlaps the board or the walls
+const X_OFFSET: i32 = 8;
+const Y_OFFSET: i32 = 4;
+const ROWS: usize = 48;
+
+// how far below its position a piece can reach with its cells and a kick
+const SHAPE_REACH: i32 = 4;
+
+struct CollisionMap {
+    rows: [[u32; ROWS]; 4], // indexed by rotation
+}
+
+impl CollisionMap {
+    fn new(board: &Board, piece: Piece, rotation_system: RotationSystemKind) -> CollisionMap {
+        let board_row = |y: i32| {
+            if (0..40).contains(&y) {
+                !(0x3ff << X_OFFSET) | (board.cells[y as usize] as u32) << X_OFFSET
+            } else {
+                !0
+            }
+        };
+
+        let mut rows = [[0; ROWS]; 4];
+        use RotationState::*;
+        for rotation in [North, East, South, West] {
+            let cells = rotation_system.cells(PieceState { piece, rotation });
+            for (i, row) in rows[rotation as usize].iter_mut().enumerate() {
+                let y = i as i32 - Y_OFFSET;
+                *row = cells.iter().fold(0, |row, &(dx, dy)| {
+                    // shift the cell onto the piece position, with walls coming in
+                    let cell_row = board_row(y + dy);
+                    row | if dx >= 0 {
+                        cell_row >> dx | !(!0 >> dx)
+                    } else {
+                        cell_row << -dx | !(!0 << -dx)
+                    }
+                });
+            }
+        }
+
+        CollisionMap { rows }
+    }
+
+    fn fits(&self, field_piece: FieldPiece) -> bool {
+        let (x, y) = field_piece.position;
+        let (x, y) = (x + X_OFFSET, y + Y_OFFSET);
+        (0..32).contains(&x)
+            && (0..ROWS as i32).contains(&y)
+            && self.rows[field_piece.piece_state.rotation as usize][y as usize] >> x & 1 == 0
+    }
+
+    // drops the piece as far as it goes
+    fn drop(&self, mut field_piece: FieldPiece) -> (FieldPiece, u32) {
+        let mut count = 0;
+        while self.fits(field_piece.move_by(0, -1)) {
+            field_piece = field_piece.move_by(0, -1);
+            count += 1;
+        }
+        (field_piece, count)
+    }
+}
+
+// whether a rotation right before the drop can make a spin (see `Board::place_field_piece`)
+// otherwise the rotation is forgotten, so that the same place is not found twice
+fn can_spin(board: &Board, map: &CollisionMap, field_piece: FieldPiece, ruleset: &Ruleset) -> bool {
+    let (x, y) = field_piece.position;
+    match field_piece.piece_state.piece {
+        Piece::T => {
+            [(-1, 1), (1, 1), (1, -1), (-1, -1)]
+                .iter()
+                .filter(|&&(dx, dy)| board.occupied(x + dx, y + dy))
+                .count()
+                >= 3
+        }
+        Piece::O => false,
+        _ => {
+            ruleset.spin_rule != SpinRule::TspinOnly
+                && [(-1, 0), (1, 0), (0, 1), (0, -1)]
+                    .iter()
+                    .all(|&(dx, dy)| !map.fits(field_piece.move_by(dx, dy)))
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct SearchKey {
+    field_piece: FieldPiece,
+    hold: bool,
+    last_rotation: Option<PieceMovement>,
+}
+
+struct SearchNode {
+    key: SearchKey,
+    time: Time,
+    parent: Option<usize>,
+    movement: Option<PieceMovement>, // None for the spawn
+    count: u32,                      // repeats of the movement (soft drops)
+    lock_delay: LockDelay,
+    fall_progress: f64,
+}
+
+#[derive(Default)]
+struct Search {
+    nodes: Vec<SearchNode>,
+    indices: HashMap<SearchKey, usize>,
+    queue: BinaryHeap<Reverse<(Time, usize)>>,
+}
+
+impl Search {
+    fn relax(&mut self, node: SearchNode) {
+        // the fastest, and then the one with the fewest move resets
+        let order = |node: &SearchNode| (node.time, node.lock_delay.resets);
+        let (key, time) = (node.key, node.time);
+        let index = match self.indices.get(&key) {
+            Some(&index) if order(&self.nodes[index]) <= order(&node) => return,
+            Some(&index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.indices.insert(key, self.nodes.len());
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        self.queue.push(Reverse((time, index)));
+    }
+}
+
+// dijkstra's algorithm over piece positions, keeping only the parents of the nodes
+// `cost` is the cost of a movement over the number of cells it moves the piece
+// NOTE: only the fastest way to each position is kept, so a slower way with fewer move resets
+// (or less fallen by gravity) that could go further is not searched
+fn search(
+    state: &State,
+    ruleset: &Ruleset,
+    cost: impl Fn(PieceMovement, u32) -> Time,
+) -> Vec<SearchNode> {
+    let Some(current_piece) = state.current_piece else {
+        return vec![];
+    };
+
+    let rotation_system = ruleset.rotation_system;
+    let held_piece = state.hold_piece.or(state.next_pieces.front().copied());
+    let maps = [
+        Some(CollisionMap::new(
+            &state.board,
+            current_piece,
+            rotation_system,
+        )),
+        held_piece.map(|piece| CollisionMap::new(&state.board, piece, rotation_system)),
+    ];
+
+    // gravity at spawn for the first unit of time
+    let spawn = |map: &CollisionMap, piece: Piece| {
+        let field_piece = FieldPiece::new_from_piece(piece, rotation_system);
+        let progress = ruleset.fall_progress(0.0, 1);
+        Some(field_piece)
+            .filter(|&field_piece| map.fits(field_piece))
+            .map(|field_piece| fall(field_piece, progress, |field_piece| map.fits(field_piece)))
+    };
+
+    let Some((spawned, fall_progress)) = spawn(maps[0].as_ref().unwrap(), current_piece) else {
+        return vec![];
+    };
+
+    let max_height = *state.board.collumn_heights.iter().max().unwrap() as i32;
+
+    let mut search = Search::default();
+    search.relax(SearchNode {
+        key: SearchKey {
+            field_piece: spawned,
+            hold: false,
+            last_rotation: None,
+        },
+        time: 0,
+        parent: None,
+        movement: None,
+        count: 0,
+        lock_delay: LockDelay::new(spawned),
+        fall_progress,
+    });
+
+    if let (Some(piece), Some(map)) = (held_piece, &maps[1]) {
+        if let Some((held_spawned, fall_progress)) = spawn(map, piece) {
+            let movement = PieceMovement::Hold;
+            search.relax(SearchNode {
+                key: SearchKey {
+                    field_piece: held_spawned,
+                    hold: true,
+                    last_rotation: None,
+                },
+                time: cost(movement, 0),
+                parent: None,
+                movement: Some(movement),
+                count: 1,
+                lock_delay: LockDelay::new(held_spawned),
+                fall_progress,
+            });
+        }
+    }
+
+    while let Some(Reverse((time, index))) = search.queue.pop() {
+        let key = search.nodes[index].key;
+        if time > search.nodes[index].time || key.field_piece.is_locked {
+            continue;
+        }
+
+        let map = maps[key.hold as usize].as_ref().unwrap();
+        let field_piece = key.field_piece;
+
+        // the piece falls by gravity during the movement
+        // None if the piece locks before the movement ends
+        let (lock_delay, fall_progress) = (
+            search.nodes[index].lock_delay,
+            search.nodes[index].fall_progress,
+        );
+        let grounded = !map.fits(field_piece.move_by(0, -1));
+        let edge = |field_piece: FieldPiece,
+                    last_rotation: Option<PieceMovement>,
+                    movement: PieceMovement,
+                    cells: u32| {
+            let (fallen, fall_progress) = if field_piece.is_locked {
+                (field_piece, 0.0)
+            } else {
+                let movement_time = ruleset.action_time.movement_time(movement, cells);
+                let progress = ruleset.fall_progress(fall_progress, movement_time);
+                fall(field_piece, progress, |field_piece| map.fits(field_piece))
+            };
+            // a fall after the rotation is the last movement, which is not a spin
+            let last_rotation = last_rotation.filter(|_| fallen.position == field_piece.position);
+            let field_piece = fallen;
+            let new_y = field_piece.position.1;
+            let move_reset = &ruleset.move_reset;
+            Some(SearchNode {
+                key: SearchKey {
+                    field_piece,
+                    hold: key.hold,
+                    last_rotation,
+                },
+                time: time + cost(movement, cells),
+                parent: Some(index),
+                movement: Some(movement),
+                count: if movement == PieceMovement::SoftDrop {
+                    cells
+                } else {
+                    1
+                },
+                lock_delay: lock_delay.after(movement, cells, grounded, new_y, move_reset)?,
+                fall_progress,
+            })
+        };
+
+        for (dx, movement, das_movement) in [
+            (-1, PieceMovement::MoveLeft, PieceMovement::DasLeft),
+            (1, PieceMovement::MoveRight, PieceMovement::DasRight),
+        ] {
+            let fits = |field_piece: FieldPiece| map.fits(field_piece);
+            let (shifted, count) = slide(field_piece, dx, ruleset.is_20g(), fits);
+            if count == 0 {
+                continue;
+            }
+            if let Some(node) = edge(field_piece.move_by(dx, 0), None, movement, 1) {
+                search.relax(node);
+            }
+            // a single cell is a tap
+            if count == 1 {
+                continue;
+            }
+            if let Some(node) = edge(shifted, None, das_movement, count) {
+                search.relax(node);
+            }
+        }
+
+        // soft drops of any number of cells, to stop under a ledge before moving or rotating
+        // high above the stack, moves and rotations (with kicks) are the same at every height
+        // (but not with gravity, as the piece reaches the stack sooner from lower)
+        let (dropped, count) = map.drop(field_piece);
+        let min_cells = if ruleset.gravity > 0.0 {
+            0
+        } else {
+            field_piece.position.1 - max_height - SHAPE_REACH
+        };
+        for cells in (1..=count).filter(|&cells| cells as i32 >= min_cells || cells == count) {
+            let new_field_piece = field_piece.move_by(0, -(cells as i32));
+            if let Some(node) = edge(new_field_piece, None, PieceMovement::SoftDrop, cells) {
+                search.relax(node);
+            }
+        }
+
+        let locked = FieldPiece {
+            is_locked: true,
+            ..dropped
+        };
+        let last_rotation = key
+            .last_rotation
+            .filter(|_| can_spin(&state.board, map, locked, ruleset));
+        if let Some(node) = edge(locked, last_rotation, PieceMovement::HardDrop, count) {
+            search.relax(node);
+        }
+
+        for movement in [
+            PieceMovement::RotateLeft,
+            PieceMovement::RotateRight,
+            PieceMovement::Rotate180,
+        ] {
+            let new_field_piece = match movement {
+                PieceMovement::RotateLeft => field_piece.rotate_left(),
+                PieceMovement::RotateRight => field_piece.rotate_right(),
+                _ if ruleset.rotate_180 => field_piece.rotate_180(),
+                _ => continue,
+            };
+
+            let kicks = rotation_system.kicks(
+                field_piece.piece_state.piece,
+                field_piece.piece_state.rotation,
+                new_field_piece.piece_state.rotation,
+            );
+
+            if let Some((srs_state, srs_field_piece)) = kicks
+                .iter()
+                .map(|kick| new_field_piece.move_by(kick.0, kick.1))
+                .enumerate()
+                .find(|(_, srs_field_piece)| map.fits(*srs_field_piece))
+            {
+                let srs_field_piece = srs_field_piece.set_super_rotation_state(srs_state as u32);
+                if let Some(node) = edge(srs_field_piece, Some(movement), movement, 1) {
+                    search.relax(node);
+                }
+            }
+        }
+    }
+
+    search.nodes
+}
+
+// the inputs to the placement in the search, ending with the hard drop
+fn path(nodes: &[SearchNode], placement: &Placement) -> Option<Vec<PieceMovement>> {
+    let key = SearchKey {
+        field_piece: placement.field_piece,
+        hold: placement.hold,
+        last_rotation: placement.last_rotation,
+    };
+    let mut index = nodes.iter().position(|node| node.key == key);
+
+    let mut movements = vec![];
+    while let Some(current) = index {
+        let node = &nodes[current];
+        if let Some(movement) = node.movement {
+            movements.extend(std::iter::repeat_n(movement, node.count as usize));
+        }
+        index = node.parent;
+    }
+
+    movements.reverse();
+    Some(movements).filter(|movements| !movements.is_empty())
+}
+
+impl State {
+    // the places of `legal_actions`, but the inputs are searched again only when needed
+    // NOTE: a spin and a plain drop into the same place are different placements
+    pub fn placements(&self, ruleset: &Ruleset) -> Vec<Placement> {
+        let action_time = &ruleset.action_time;
+        let nodes = search(self, ruleset, |movement, cells| {
+            action_time.movement_time(movement, cells)
+        });
+        self.placements_in(&nodes, ruleset)
+    }
+
+    // every state after the current piece is placed, with the fastest inputs
+    pub fn legal_actions(&self, ruleset: &Ruleset) -> Vec<State> {
+        let action_time = &ruleset.action_time;
+        let nodes = search(self, ruleset, |movement, cells| {
+            action_time.movement_time(movement, cells)
+        });
+        self.placements_in(&nodes, ruleset)
+            .iter()
+            .map(|placement| {
+                let mut state = self.place(placement, ruleset);
+                state.last_action.as_mut().unwrap().movements_history =
+                    path(&nodes, placement).unwrap_or_default();
+                state
+            })
+            .collect()
+    }
+
+    fn placements_in(&self, nodes: &[SearchNode], ruleset: &Ruleset) -> Vec<Placement> {
+        let mut placements: Vec<Placement> = vec![];
+        let mut indices: HashMap<_, usize> = HashMap::new();
+
+        // the same board can be reached in another rotation (O, I, S and Z) or with another kick,
+        // so only the fastest placement of each resulting state and spin is kept
+        for node in nodes {
+            if !node.key.field_piece.is_locked {
+                continue;
+            }
+
+            let placement = Placement {
+                field_piece: node.key.field_piece,
+                hold: node.key.hold,
+                last_rotation: node.key.last_rotation,
+                time: node.time,
+            };
+
+            let mut cells = placement.field_piece.cells();
+            cells.sort();
+            let hold_piece = if placement.hold {
+                self.current_piece
+            } else {
+                self.hold_piece
+            };
+            // a rotation is only kept for spins (see `can_spin`), so the other kinds follow the cells
+            let spin = placement.last_rotation.map(|last_rotation| {
+                self.board
+                    .place_field_piece(placement.field_piece, Some(last_rotation), ruleset)
+                    .1
+            });
+
+            match indices.get(&(cells, hold_piece, spin)) {
+                Some(&index) if placements[index].time <= placement.time => {}
+                Some(&index) => placements[index] = placement,
+                None => {
+                    indices.insert((cells, hold_piece, spin), placements.len());
+                    placements.push(placement);
+                }
+            }
+        }
+
+        placements
+    }
+
+    // the movements history of the resulting last action is left empty
+    pub fn place(&self, placement: &Placement, ruleset: &Ruleset) -> State {
+        let mut movement_state = MovementState::new_from_piece(
+            self.current_piece.unwrap(),
+            self.hold_piece,
+            self.next_pieces.clone(),
+            ruleset.rotation_system,
+        );
+        if placement.hold {
+            movement_state = movement_state.hold(ruleset).unwrap();
+        }
+        movement_state.field_piece = placement.field_piece;
+        movement_state.movements_history = vec![];
+
+        let (new_board, placement_kind) =
+            self.board
+                .place_field_piece(placement.field_piece, placement.last_rotation, ruleset);
+        self.state_after(
+            movement_state,
+            new_board,
+            placement_kind,
+            placement.time,
+            ruleset,
+        )
+    }
+
+    // the fastest inputs for the placement, ending with the hard drop
+    pub fn movements(
+        &self,
+        placement: &Placement,
+        ruleset: &Ruleset,
+    ) -> Option<Vec<PieceMovement>> {
+        let action_time = &ruleset.action_time;
+        let nodes = search(self, ruleset, |movement, cells| {
+            action_time.movement_time(movement, cells)
+        });
+        path(&nodes, placement)
+    }
+
+    // the inputs with the fewest key presses for the placement (the fastest of them)
+    // NOTE: consecutive soft drops are a single key held down
+    pub fn finesse(&self, placement: &Placement, ruleset: &Ruleset) -> Option<Vec<PieceMovement>> {
+        // a key press outweighs any time
+        const KEY_PRESS: Time = 1 << 16;
+
+        let action_time = &ruleset.action_time;
+        let nodes = search(self, ruleset, |movement, cells| {
+            KEY_PRESS + action_time.movement_time(movement, cells)
+        });
+        path(&nodes, placement)
+    }
+}
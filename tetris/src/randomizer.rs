@@ -0,0 +1,203 @@
+use crate::*;
+use rand::{seq::SliceRandom, Rng, RngCore};
+use std::{collections::VecDeque, fmt, str::FromStr};
+use strum::IntoEnumIterator;
+
+// generates the piece queue of a game
+// the state of a randomizer (e.g. the rest of the bag) belongs to the game, not to a `State`
+pub trait Randomizer {
+    fn next_piece(&mut self, rng: &mut dyn RngCore) -> Piece;
+}
+
+impl<R: Randomizer + ?Sized> Randomizer for Box<R> {
+    fn next_piece(&mut self, rng: &mut dyn RngCore) -> Piece {
+        (**self).next_piece(rng)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RandomizerKind {
+    #[default]
+    SevenBag,
+    FourteenBag,
+    Random,
+    Nes,
+    Tgm,
+    Fixed(String), // e.g. "TIOSZLJ", repeated when exhausted
+}
+
+impl RandomizerKind {
+    pub fn build(&self) -> Result<Box<dyn Randomizer + Send>, FixedSequenceError> {
+        Ok(match self {
+            RandomizerKind::SevenBag => Box::new(Bag::new(1)),
+            RandomizerKind::FourteenBag => Box::new(Bag::new(2)),
+            RandomizerKind::Random => Box::new(PureRandom),
+            RandomizerKind::Nes => Box::new(Nes::default()),
+            RandomizerKind::Tgm => Box::new(Tgm::default()),
+            RandomizerKind::Fixed(sequence) => Box::new(sequence.parse::<FixedSequence>()?),
+        })
+    }
+
+    // for rulesets loaded from a file
+    pub fn validate(&self) -> Result<(), String> {
+        self.build().map(|_| ()).map_err(|error| error.to_string())
+    }
+}
+
+// `copies` sets of the seven pieces are shuffled together
+pub struct Bag {
+    copies: usize,
+    rest: Vec<Piece>,
+}
+
+impl Bag {
+    pub fn new(copies: usize) -> Self {
+        Bag {
+            copies,
+            rest: vec![],
+        }
+    }
+}
+
+impl Randomizer for Bag {
+    fn next_piece(&mut self, rng: &mut dyn RngCore) -> Piece {
+        if self.rest.is_empty() {
+            self.rest = (0..self.copies).flat_map(|_| Piece::iter()).collect();
+            self.rest.shuffle(rng);
+        }
+
+        self.rest.pop().unwrap()
+    }
+}
+
+pub struct PureRandom;
+
+impl Randomizer for PureRandom {
+    fn next_piece(&mut self, rng: &mut dyn RngCore) -> Piece {
+        Piece::iter().nth(rng.gen_range(0..7)).unwrap()
+    }
+}
+
+// rolls an 8-sided die, and rerolls once (7-sided) if it hits the extra side or repeats the last piece
+#[derive(Default)]
+pub struct Nes {
+    last: Option<Piece>,
+}
+
+impl Randomizer for Nes {
+    fn next_piece(&mut self, rng: &mut dyn RngCore) -> Piece {
+        let mut piece = Piece::iter().nth(rng.gen_range(0..8));
+        if piece.is_none() || piece == self.last {
+            piece = Piece::iter().nth(rng.gen_range(0..7));
+        }
+
+        self.last = piece;
+        piece.unwrap()
+    }
+}
+
+// tgm2: rolls up to 6 times for a piece not in the last 4 pieces
+pub struct Tgm {
+    history: VecDeque<Piece>,
+    first: bool,
+}
+
+const TGM_ROLLS: usize = 6;
+
+impl Default for Tgm {
+    fn default() -> Self {
+        use Piece::*;
+        Tgm {
+            history: VecDeque::from(vec![Z, S, S, Z]),
+            first: true,
+        }
+    }
+}
+
+impl Randomizer for Tgm {
+    fn next_piece(&mut self, rng: &mut dyn RngCore) -> Piece {
+        use Piece::*;
+
+        let piece = if self.first {
+            // the first piece is never S, Z or O
+            self.first = false;
+            *[I, J, L, T].choose(rng).unwrap()
+        } else {
+            let mut piece = I;
+            for _ in 0..TGM_ROLLS {
+                piece = Piece::iter().nth(rng.gen_range(0..7)).unwrap();
+                if !self.history.contains(&piece) {
+                    break;
+                }
+            }
+            piece
+        };
+
+        self.history.pop_front();
+        self.history.push_back(piece);
+        piece
+    }
+}
+
+// for deterministic scenarios
+pub struct FixedSequence {
+    sequence: Vec<Piece>,
+    index: usize,
+}
+
+impl FixedSequence {
+    pub fn new(sequence: Vec<Piece>) -> Self {
+        assert!(!sequence.is_empty(), "a fixed sequence needs a piece");
+        FixedSequence { sequence, index: 0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FixedSequenceError {
+    Empty,
+    InvalidPiece(char), // the first character that is not a piece
+}
+
+impl fmt::Display for FixedSequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FixedSequenceError::Empty => write!(f, "the fixed sequence is empty"),
+            FixedSequenceError::InvalidPiece(c) => {
+                write!(f, "{:?} in the fixed sequence is not a piece", c)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FixedSequenceError {}
+
+impl FromStr for FixedSequence {
+    type Err = FixedSequenceError;
+
+    fn from_str(sequence: &str) -> Result<Self, FixedSequenceError> {
+        let sequence = sequence
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| {
+                c.to_string()
+                    .parse::<Piece>()
+                    .map_err(|_| FixedSequenceError::InvalidPiece(c))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if sequence.is_empty() {
+            return Err(FixedSequenceError::Empty);
+        }
+
+        Ok(FixedSequence::new(sequence))
+    }
+}
+
+impl Randomizer for FixedSequence {
+    fn next_piece(&mut self, _rng: &mut dyn RngCore) -> Piece {
+        let piece = self.sequence[self.index];
+        self.index = (self.index + 1) % self.sequence.len();
+        piece
+    }
+}
@@ -0,0 +1,8 @@
+use rand::SeedableRng;
+
+// a small and fast rng, so that a game can be replayed from its seed
+pub type GameRng = rand_pcg::Pcg64Mcg;
+
+pub fn seeded_rng(seed: u64) -> GameRng {
+    GameRng::seed_from_u64(seed)
+}
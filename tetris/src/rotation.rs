@@ -0,0 +1,303 @@
+use crate::*;
+
+pub trait RotationSystem {
+    fn cells(&self, piece_state: PieceState) -> [Position; 4];
+    fn initial_position(&self, piece: Piece) -> Position;
+    // offsets tested in order when rotating from `from` to `to`
+    fn kicks(&self, piece: Piece, from: RotationState, to: RotationState) -> &'static [Position];
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RotationSystemKind {
+    #[default]
+    Srs,
+    SrsPlus,
+    Ars,
+    Nrs,
+}
+
+impl RotationSystem for RotationSystemKind {
+    fn cells(&self, piece_state: PieceState) -> [Position; 4] {
+        match self {
+            RotationSystemKind::Srs => Srs.cells(piece_state),
+            RotationSystemKind::SrsPlus => SrsPlus.cells(piece_state),
+            RotationSystemKind::Ars => Ars.cells(piece_state),
+            RotationSystemKind::Nrs => Nrs.cells(piece_state),
+        }
+    }
+
+    fn initial_position(&self, piece: Piece) -> Position {
+        match self {
+            RotationSystemKind::Srs => Srs.initial_position(piece),
+            RotationSystemKind::SrsPlus => SrsPlus.initial_position(piece),
+            RotationSystemKind::Ars => Ars.initial_position(piece),
+            RotationSystemKind::Nrs => Nrs.initial_position(piece),
+        }
+    }
+
+    fn kicks(&self, piece: Piece, from: RotationState, to: RotationState) -> &'static [Position] {
+        match self {
+            RotationSystemKind::Srs => Srs.kicks(piece, from, to),
+            RotationSystemKind::SrsPlus => SrsPlus.kicks(piece, from, to),
+            RotationSystemKind::Ars => Ars.kicks(piece, from, to),
+            RotationSystemKind::Nrs => Nrs.kicks(piece, from, to),
+        }
+    }
+}
+
+fn srs_transition_180(from: RotationState, to: RotationState) -> Option<usize> {
+    use RotationState::*;
+    match (from, to) {
+        (North, South) => Some(0),
+        (East, West) => Some(1),
+        (South, North) => Some(2),
+        (West, East) => Some(3),
+        _ => None,
+    }
+}
+
+fn srs_transition(from: RotationState, to: RotationState) -> Option<usize> {
+    use RotationState::*;
+    match (from, to) {
+        (North, East) => Some(0),
+        (East, North) => Some(1),
+        (East, South) => Some(2),
+        (South, East) => Some(3),
+        (South, West) => Some(4),
+        (West, South) => Some(5),
+        (West, North) => Some(6),
+        (North, West) => Some(7),
+        _ => None,
+    }
+}
+
+const SRS_KICK_TABLE_NORMAL: [[Position; 5]; 8] = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // North -> East
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // East -> North
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // East -> South
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // South -> East
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // South -> West
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // West -> South
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // West -> North
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // North -> West
+];
+
+// guideline SRS
+// NOTE: 180 rotations are not part of the guideline, so they never kick here
+pub struct Srs;
+
+impl RotationSystem for Srs {
+    fn cells(&self, piece_state: PieceState) -> [Position; 4] {
+        piece_state.cells()
+    }
+
+    fn initial_position(&self, piece: Piece) -> Position {
+        // NOTE: this is configured for tetrio
+        // TODO: confirm this is correct
+        match piece {
+            Piece::S => (4, 20),
+            Piece::Z => (4, 20),
+            Piece::J => (4, 20),
+            Piece::L => (4, 20),
+            Piece::T => (4, 20),
+            Piece::O => (4, 21),
+            Piece::I => (4, 20),
+        }
+    }
+
+    fn kicks(&self, piece: Piece, from: RotationState, to: RotationState) -> &'static [Position] {
+        const KICK_TABLE_I: [[Position; 5]; 8] = [
+            [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], // North -> East
+            [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // East -> North
+            [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // East -> South
+            [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // South -> East
+            [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // South -> West
+            [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)], // West -> South
+            [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // West -> North
+            [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // North -> West
+        ];
+
+        let Some(transition) = srs_transition(from, to) else {
+            return &[(0, 0)];
+        };
+
+        match piece {
+            Piece::O => &[(0, 0)],
+            Piece::I => &KICK_TABLE_I[transition],
+            _ => &SRS_KICK_TABLE_NORMAL[transition],
+        }
+    }
+}
+
+// tetrio SRS+ (the I piece kicks are left-right symmetric)
+pub struct SrsPlus;
+
+impl RotationSystem for SrsPlus {
+    fn cells(&self, piece_state: PieceState) -> [Position; 4] {
+        Srs.cells(piece_state)
+    }
+
+    fn initial_position(&self, piece: Piece) -> Position {
+        Srs.initial_position(piece)
+    }
+
+    fn kicks(&self, piece: Piece, from: RotationState, to: RotationState) -> &'static [Position] {
+        const KICK_TABLE_I: [[Position; 5]; 8] = [
+            [(0, 0), (1, 0), (-2, 0), (-2, -1), (1, 2)], // North -> East
+            [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)], // East -> North
+            [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)], // East -> South
+            [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)], // South -> East
+            [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)], // South -> West
+            [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)], // West -> South
+            [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)], // West -> North
+            [(0, 0), (-1, 0), (2, 0), (2, -1), (-1, 2)], // North -> West
+        ];
+
+        const KICK_TABLE_180: [[Position; 6]; 4] = [
+            [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)], // North -> South
+            [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],   // East -> West
+            [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)], // South -> North
+            [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)], // West -> East
+        ];
+
+        if piece == Piece::O {
+            return &[(0, 0)];
+        }
+
+        if let Some(transition) = srs_transition_180(from, to) {
+            return &KICK_TABLE_180[transition];
+        }
+
+        match (piece, srs_transition(from, to)) {
+            (Piece::I, Some(transition)) => &KICK_TABLE_I[transition],
+            _ => Srs.kicks(piece, from, to),
+        }
+    }
+}
+
+// arika ARS (TGM)
+// TODO: the center column rule of J, L and T is not supported
+pub struct Ars;
+
+impl RotationSystem for Ars {
+    fn cells(&self, piece_state: PieceState) -> [Position; 4] {
+        // indexed by [piece][rotation]; the spawn orientation is flat side up
+        const CELLS: [[[Position; 4]; 4]; 7] = [
+            [
+                [(0, 0), (1, 0), (-1, -1), (0, -1)], // S
+                [(-1, 1), (-1, 0), (0, 0), (0, -1)],
+                [(0, 0), (1, 0), (-1, -1), (0, -1)],
+                [(-1, 1), (-1, 0), (0, 0), (0, -1)],
+            ],
+            [
+                [(-1, 0), (0, 0), (0, -1), (1, -1)], // Z
+                [(1, 1), (0, 0), (1, 0), (0, -1)],
+                [(-1, 0), (0, 0), (0, -1), (1, -1)],
+                [(1, 1), (0, 0), (1, 0), (0, -1)],
+            ],
+            [
+                [(-1, 0), (0, 0), (1, 0), (1, -1)], // J
+                [(0, 1), (0, 0), (-1, -1), (0, -1)],
+                [(-1, 0), (-1, -1), (0, -1), (1, -1)],
+                [(0, 1), (1, 1), (0, 0), (0, -1)],
+            ],
+            [
+                [(-1, 0), (0, 0), (1, 0), (-1, -1)], // L
+                [(-1, 1), (0, 1), (0, 0), (0, -1)],
+                [(1, 0), (-1, -1), (0, -1), (1, -1)],
+                [(0, 1), (0, 0), (0, -1), (1, -1)],
+            ],
+            [
+                [(-1, 0), (0, 0), (1, 0), (0, -1)], // T
+                [(0, 1), (-1, 0), (0, 0), (0, -1)],
+                [(0, 0), (-1, -1), (0, -1), (1, -1)],
+                [(0, 1), (0, 0), (1, 0), (0, -1)],
+            ],
+            [[(0, 0), (1, 0), (0, -1), (1, -1)]; 4], // O
+            [
+                [(-1, 0), (0, 0), (1, 0), (2, 0)], // I
+                [(1, 1), (1, 0), (1, -1), (1, -2)],
+                [(-1, 0), (0, 0), (1, 0), (2, 0)],
+                [(1, 1), (1, 0), (1, -1), (1, -2)],
+            ],
+        ];
+
+        CELLS[piece_state.piece as usize][piece_state.rotation as usize]
+    }
+
+    fn initial_position(&self, piece: Piece) -> Position {
+        match piece {
+            Piece::I => (4, 20),
+            _ => (4, 21),
+        }
+    }
+
+    fn kicks(&self, piece: Piece, _: RotationState, _: RotationState) -> &'static [Position] {
+        match piece {
+            Piece::I | Piece::O => &[(0, 0)],
+            _ => &[(0, 0), (1, 0), (-1, 0)],
+        }
+    }
+}
+
+// nintendo NRS (NES), which has no kicks
+pub struct Nrs;
+
+impl RotationSystem for Nrs {
+    fn cells(&self, piece_state: PieceState) -> [Position; 4] {
+        // indexed by [piece][rotation]; the spawn orientation is flat side up
+        const CELLS: [[[Position; 4]; 4]; 7] = [
+            [
+                [(0, 0), (1, 0), (-1, -1), (0, -1)], // S
+                [(0, 1), (0, 0), (1, 0), (1, -1)],
+                [(0, 0), (1, 0), (-1, -1), (0, -1)],
+                [(0, 1), (0, 0), (1, 0), (1, -1)],
+            ],
+            [
+                [(-1, 0), (0, 0), (0, -1), (1, -1)], // Z
+                [(1, 1), (0, 0), (1, 0), (0, -1)],
+                [(-1, 0), (0, 0), (0, -1), (1, -1)],
+                [(1, 1), (0, 0), (1, 0), (0, -1)],
+            ],
+            [
+                [(-1, 0), (0, 0), (1, 0), (1, -1)], // J
+                [(0, 1), (0, 0), (0, -1), (-1, -1)],
+                [(1, 0), (0, 0), (-1, 0), (-1, 1)],
+                [(0, -1), (0, 0), (0, 1), (1, 1)],
+            ],
+            [
+                [(-1, 0), (0, 0), (1, 0), (-1, -1)], // L
+                [(0, 1), (0, 0), (0, -1), (-1, 1)],
+                [(1, 0), (0, 0), (-1, 0), (1, 1)],
+                [(0, -1), (0, 0), (0, 1), (1, -1)],
+            ],
+            [
+                [(-1, 0), (0, 0), (1, 0), (0, -1)], // T
+                [(0, 1), (0, 0), (0, -1), (-1, 0)],
+                [(1, 0), (0, 0), (-1, 0), (0, 1)],
+                [(0, -1), (0, 0), (0, 1), (1, 0)],
+            ],
+            [[(0, 0), (1, 0), (0, -1), (1, -1)]; 4], // O
+            [
+                [(-2, 0), (-1, 0), (0, 0), (1, 0)], // I
+                [(0, 1), (0, 0), (0, -1), (0, -2)],
+                [(-2, 0), (-1, 0), (0, 0), (1, 0)],
+                [(0, 1), (0, 0), (0, -1), (0, -2)],
+            ],
+        ];
+
+        CELLS[piece_state.piece as usize][piece_state.rotation as usize]
+    }
+
+    fn initial_position(&self, piece: Piece) -> Position {
+        match piece {
+            Piece::I => (5, 20),
+            _ => (5, 21),
+        }
+    }
+
+    fn kicks(&self, _: Piece, _: RotationState, _: RotationState) -> &'static [Position] {
+        &[(0, 0)]
+    }
+}
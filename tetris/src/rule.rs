@@ -1,76 +1,400 @@
 use crate::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
+// rules that differ between clients
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct Ruleset {
+    pub rotation_system: RotationSystemKind,
+    pub randomizer: RandomizerKind,
+    pub rotate_180: bool,
+    pub preview: usize, // the number of next pieces shown to the player
+    pub spin_rule: SpinRule,
+    pub action_time: ActionTime,
+    pub move_reset: MoveReset,
+    pub gravity: f64, // cells per unit of time (20.0 or more: 20G)
+    pub attack_table: AttackTable,
+    pub combo_table: ComboTable,
+    pub special_attack: SpecialAttack,
+    pub garbage: GarbageRule,
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Ruleset {
+            rotation_system: RotationSystemKind::default(),
+            randomizer: RandomizerKind::default(),
+            rotate_180: false,
+            preview: 5,
+            spin_rule: SpinRule::TspinOnly,
+            action_time: ActionTime::default(),
+            move_reset: MoveReset::default(),
+            gravity: 0.0,
+            attack_table: AttackTable::default(),
+            combo_table: ComboTable::Table(vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5]),
+            special_attack: SpecialAttack::default(),
+            garbage: GarbageRule::default(),
+        }
+    }
+}
+
+impl Ruleset {
+    pub fn tetrio() -> Ruleset {
+        Ruleset {
+            rotation_system: RotationSystemKind::SrsPlus,
+            rotate_180: true,
+            combo_table: ComboTable::Multiplier,
+            special_attack: SpecialAttack {
+                b2b: B2bAttack::Chaining,
+                ..Default::default()
+            },
+            // the garbage cap and speed (in frames) of tetra league
+            // NOTE: the holes are not modelled after tetrio, since its messiness is not confirmed
+            garbage: GarbageRule {
+                delay: 20,
+                cap: Some(8),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    // b2b chaining was replaced with surge and all-mini in season 2
+    pub fn tetrio_season2() -> Ruleset {
+        Ruleset {
+            spin_rule: SpinRule::AllMini,
+            special_attack: SpecialAttack {
+                b2b: B2bAttack::Flat(1),
+                surge: Some(4),
+                ..Default::default()
+            },
+            ..Ruleset::tetrio()
+        }
+    }
+
+    pub fn jstris() -> Ruleset {
+        Ruleset {
+            // NOTE: the 180 rotation of jstris has no kicks, which is what `Srs` does with it
+            rotation_system: RotationSystemKind::Srs,
+            rotate_180: true,
+            combo_table: ComboTable::Table(vec![0, 0, 1, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5]),
+            ..Default::default()
+        }
+    }
+
+    // the guideline tables: tetris 4, t-spin single / double / triple 2 / 4 / 6, b2b +1,
+    // combos of 1, 1, 2, 2, 3, 3, 4, 4, 4, 5 lines from the second clear, perfect clear 10
+    // NOTE: written out, so that a change of the default does not change this preset
+    pub fn puyo_puyo_tetris() -> Ruleset {
+        Ruleset {
+            rotation_system: RotationSystemKind::Srs,
+            rotate_180: false,
+            preview: 5,
+            spin_rule: SpinRule::TspinOnly,
+            attack_table: AttackTable {
+                clear1: 0,
+                clear2: 1,
+                clear3: 2,
+                clear4: 4,
+                mini_tspin: 0,
+                mini_tspin1: 0,
+                mini_tspin2: 1,
+                tspin: 0,
+                tspin1: 2,
+                tspin2: 4,
+                tspin3: 6,
+            },
+            combo_table: ComboTable::Table(vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5]),
+            special_attack: SpecialAttack {
+                b2b: B2bAttack::Flat(1),
+                perfect_clear: 10,
+                surge: None,
+                spin_b2b: true,
+            },
+            ..Default::default()
+        }
+    }
+
+    // the same tables as puyo puyo tetris, with six next pieces
+    pub fn tetris_99() -> Ruleset {
+        Ruleset {
+            preview: 6,
+            ..Ruleset::puyo_puyo_tetris()
+        }
+    }
+
+    pub fn from_preset(name: &str) -> Option<Ruleset> {
+        match name {
+            "guideline" => Some(Ruleset::default()),
+            "tetrio" => Some(Ruleset::tetrio()),
+            "tetrio_season2" => Some(Ruleset::tetrio_season2()),
+            "jstris" => Some(Ruleset::jstris()),
+            "ppt" => Some(Ruleset::puyo_puyo_tetris()),
+            "tetris99" => Some(Ruleset::tetris_99()),
+            _ => None,
+        }
+    }
+
+    // the settings that cannot be checked by their types
+    pub fn validate(&self) -> Result<(), String> {
+        self.randomizer.validate()
+    }
+
+    // 20G: the piece is on the ground at spawn and after every movement
+    pub fn is_20g(&self) -> bool {
+        self.gravity >= 20.0
+    }
+
+    // progress: the part of a cell the piece has fallen so far (see `fall`)
+    pub fn fall_progress(&self, progress: f64, time: Time) -> f64 {
+        if self.is_20g() {
+            f64::INFINITY
+        } else {
+            progress + self.gravity * time as f64
+        }
+    }
+
+    pub fn base_attack(&self, placement_kind: PlacementKind) -> u32 {
+        use PlacementKind::*;
+        let table = &self.attack_table;
+        match placement_kind {
+            None => 0,
+            Clear1 => table.clear1,
+            Clear2 => table.clear2,
+            Clear3 => table.clear3,
+            Clear4 => table.clear4,
+            MiniTspin => table.mini_tspin,
+            MiniTspin1 => table.mini_tspin1,
+            MiniTspin2 => table.mini_tspin2,
+            Tspin => table.tspin,
+            Tspin1 => table.tspin1,
+            Tspin2 => table.tspin2,
+            Tspin3 => table.tspin3,
+            // there are no t-spin triples of minis and no t-spin quads
+            MiniSpin { lines, .. } => match lines {
+                0 => table.mini_tspin,
+                1 => table.mini_tspin1,
+                2 => table.mini_tspin2,
+                3 => table.clear3,
+                _ => table.clear4,
+            },
+            Spin { lines, .. } => match lines {
+                0 => table.tspin,
+                1 => table.tspin1,
+                2 => table.tspin2,
+                3 => table.tspin3,
+                _ => table.clear4,
+            },
+        }
+    }
+
+    // garbage sent by a clear of `combo` consecutive clears (including this one) without any other bonus
+    pub fn combo_attack(&self, combo: u32) -> u32 {
+        self.attack(PlacementKind::None, combo, 0)
+    }
+
+    // b2b: the number of b2b clears in a row including this one (0 if this clear is not b2b)
+    pub fn attack(&self, placement_kind: PlacementKind, combo: u32, b2b: u32) -> u32 {
+        let attack = self.base_attack(placement_kind) + self.special_attack.b2b.bonus(b2b);
+
+        match &self.combo_table {
+            ComboTable::Table(table) => {
+                let index = (combo as usize).min(table.len().saturating_sub(1));
+                attack + table.get(index).copied().unwrap_or(0)
+            }
+            ComboTable::Multiplier => {
+                // tetrio counts the first clear as combo 0
+                let combo = combo.saturating_sub(1) as f64;
+                if attack > 0 {
+                    (attack as f64 * (1.0 + 0.25 * combo)).floor() as u32
+                } else if combo >= 2.0 {
+                    (1.25 * combo).ln_1p().floor() as u32
+                } else {
+                    0
+                }
+            }
+        }
+    }
+}
+
+// how spins of pieces other than T are rewarded
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SpinRule {
+    TspinOnly,
+    AllMini, // counted as t-spin minis (tetrio)
+    AllSpin, // counted as t-spins
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ComboTable {
+    Table(Vec<u32>), // the last value is used for longer combos
+    Multiplier,      // tetrio
+}
+
+// in frames (60 per second)
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
 pub struct ActionTime {
     pub move_one: Time,
+    pub das: Time, // until the piece reaches the second cell when a move key is held
+    pub arr: Time, // per cell after that (0: instantly to the wall)
+    pub soft_drop: Time, // per cell (the soft drop factor)
     pub hard_drop: Time,
     pub rotate: Time,
+    pub rotate_180: Time,
     pub hold: Time,
-    pub place: Time,
-    pub single: Time,
+    pub place: Time,  // the entry delay until the next piece spawns
+    pub single: Time, // the line clear delays, which replace the entry delay
     pub double: Time,
     pub triple: Time,
     pub tetris: Time,
     pub perfect_clear: Time,
 }
 
-// TODO: make the value correct
-pub const DEFAULT_ACTION_TIME: ActionTime = ActionTime {
-    move_one: 1,
-    hard_drop: 1,
-    rotate: 1,
-    hold: 1,
-    place: 6,
-    single: 50,
-    double: 50,
-    triple: 50,
-    tetris: 50,
-    perfect_clear: 1,
-};
-
-// TODO: make the value correct
-pub const SAME_HOLE_POSITION_RATE: f64 = 0.7;
-
-pub fn base_attack(placement_kind: PlacementKind) -> u32 {
-    use PlacementKind::*;
-    match placement_kind {
-        None => 0,
-        Clear1 => 0,
-        Clear2 => 1,
-        Clear3 => 2,
-        Clear4 => 4,
-        MiniTspin => 0,
-        MiniTspin1 => 0,
-        MiniTspin2 => 1,
-        Tspin => 0,
-        Tspin1 => 2,
-        Tspin2 => 4,
-        Tspin3 => 6,
-    }
-}
-
-pub fn combo_attack(combo: u32) -> u32 {
-    match combo {
-        0 => 0,
-        1 => 0,
-        2 => 1,
-        3 => 1,
-        4 => 2,
-        5 => 2,
-        6 => 3,
-        7 => 3,
-        8 => 4,
-        9 => 4,
-        10 => 4,
-        _ => 5,
+// the guideline handling: das 10 and arr 2, the soft drop 20 times the gravity of level 1,
+// an entry delay of 6 and a line clear delay of 40
+impl Default for ActionTime {
+    fn default() -> Self {
+        ActionTime {
+            move_one: 1,
+            das: 10,
+            arr: 2,
+            soft_drop: 3,
+            hard_drop: 1,
+            rotate: 1,
+            rotate_180: 1,
+            hold: 1,
+            place: 6,
+            single: 40,
+            double: 40,
+            triple: 40,
+            tetris: 40,
+            perfect_clear: 40,
+        }
+    }
+}
+
+impl ActionTime {
+    // cells: the number of cells the piece is moved
+    pub fn movement_time(&self, movement: PieceMovement, cells: u32) -> Time {
+        match movement {
+            PieceMovement::MoveLeft | PieceMovement::MoveRight => self.move_one,
+            PieceMovement::DasLeft | PieceMovement::DasRight => {
+                self.das + self.arr * cells.saturating_sub(2)
+            }
+            PieceMovement::SoftDrop => self.soft_drop * cells,
+            PieceMovement::HardDrop => self.hard_drop,
+            PieceMovement::RotateLeft | PieceMovement::RotateRight => self.rotate,
+            PieceMovement::Rotate180 => self.rotate_180,
+            PieceMovement::Hold => self.hold,
+        }
     }
 }
 
+// guideline lock delay: moves and rotations on the ground restart it a limited number of times
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct MoveReset {
+    pub limit: Option<u32>,  // None: infinite
+    pub infinite_spin: bool, // rotations are not counted
+}
+
+impl Default for MoveReset {
+    fn default() -> Self {
+        MoveReset {
+            limit: Some(15),
+            infinite_spin: false,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AttackTable {
+    pub clear1: u32,
+    pub clear2: u32,
+    pub clear3: u32,
+    pub clear4: u32,
+    pub mini_tspin: u32,
+    pub mini_tspin1: u32,
+    pub mini_tspin2: u32,
+    pub tspin: u32,
+    pub tspin1: u32,
+    pub tspin2: u32,
+    pub tspin3: u32,
+}
+
+impl Default for AttackTable {
+    fn default() -> Self {
+        AttackTable {
+            clear1: 0,
+            clear2: 1,
+            clear3: 2,
+            clear4: 4,
+            mini_tspin: 0,
+            mini_tspin1: 0,
+            mini_tspin2: 1,
+            tspin: 0,
+            tspin1: 2,
+            tspin2: 4,
+            tspin3: 6,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
 pub struct SpecialAttack {
-    pub b2b: u32,
+    pub b2b: B2bAttack,
     pub perfect_clear: u32,
+    // when a b2b of at least this many is broken, the b2b is released as garbage
+    pub surge: Option<u32>,
+    pub spin_b2b: bool, // spins of pieces other than T (see `SpinRule`) continue the b2b chain
+}
+
+impl Default for SpecialAttack {
+    fn default() -> Self {
+        SpecialAttack {
+            b2b: B2bAttack::Flat(1),
+            perfect_clear: 10,
+            surge: None,
+            spin_b2b: true,
+        }
+    }
+}
+
+impl SpecialAttack {
+    // b2b_chain: the number of consecutive b2b clears just before the chain is broken
+    pub fn surge(&self, b2b_chain: u32) -> u32 {
+        // the first clear of the chain is not a b2b
+        let b2b = b2b_chain.saturating_sub(1);
+        match self.surge {
+            Some(threshold) if b2b >= threshold => b2b,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum B2bAttack {
+    Flat(u32),
+    Chaining, // tetrio: the bonus grows with the length of the chain
 }
 
-pub const DEFAULT_SPECIAL_ATTACK: SpecialAttack = SpecialAttack {
-    b2b: 1,
-    perfect_clear: 10,
-};
+impl B2bAttack {
+    pub fn bonus(&self, b2b: u32) -> u32 {
+        if b2b == 0 {
+            return 0;
+        }
+
+        match self {
+            B2bAttack::Flat(bonus) => *bonus,
+            // levels: 1-2 => 1, 3-7 => 2, 8-23 => 3, 24-66 => 4, 67-184 => 5, ...
+            B2bAttack::Chaining => (1.0 + (b2b as f64 * 0.8).ln_1p()).floor() as u32,
+        }
+    }
+}
@@ -1,31 +1,45 @@
 use crate::*;
-use rand::{seq::SliceRandom, thread_rng};
-use std::collections::{BinaryHeap, HashMap, VecDeque};
+use rand::Rng;
+use std::collections::VecDeque;
 use strum::IntoEnumIterator;
 
+// the number of pieces drawn from the randomizer at a time (a bag)
+const NEXT_PIECES_CHUNK: usize = 7;
+
+const ALL_PIECES: u8 = 0x7f;
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct State {
     pub board: Board,
     pub current_piece: Option<Piece>,
     pub hold_piece: Option<Piece>,
     pub next_pieces: VecDeque<Piece>,
-    pub b2b: bool, // as a state
+    pub b2b_chain: u32, // the number of consecutive b2b enabled clears (0: not in b2b)
+    pub bag: u8,        // pieces left in the 7-bag of the last next piece (0: a new bag starts)
     pub last_action: Option<LastAction>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub garbage_queue: VecDeque<Garbage>, // incoming garbage, the oldest first
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LastAction {
     pub placement_kind: PlacementKind,
     pub placed_piece: Piece,
-    pub b2b: bool, // as an action
+    pub field_piece: FieldPiece, // as it was locked
+    pub b2b: bool,               // as an action
+    pub b2b_chain: u32,
     pub combo: u32,
     pub perfect_clear: bool,
     pub garbage_sent: u32,
+    pub garbage_cancelled: u32, // of the garbage sent, the lines that cancelled incoming garbage
     pub time: Time,
     pub movements_history: Vec<PieceMovement>,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PlacementKind {
     None,
     Clear1,
@@ -39,117 +53,245 @@ pub enum PlacementKind {
     Tspin1,
     Tspin2,
     Tspin3,
+    MiniSpin { piece: Piece, lines: u32 }, // spins of pieces other than T (all-mini)
+    Spin { piece: Piece, lines: u32 },     // spins of pieces other than T (all-spin)
+}
+
+impl PlacementKind {
+    pub fn cleared_lines(&self) -> u32 {
+        use PlacementKind::*;
+        match self {
+            None | MiniTspin | Tspin => 0,
+            Clear1 | MiniTspin1 | Tspin1 => 1,
+            Clear2 | MiniTspin2 | Tspin2 => 2,
+            Clear3 | Tspin3 => 3,
+            Clear4 => 4,
+            MiniSpin { lines, .. } | Spin { lines, .. } => *lines,
+        }
+    }
 }
 
-fn is_b2b_enabled(placement_kind: PlacementKind) -> bool {
+fn is_b2b_enabled(placement_kind: PlacementKind, ruleset: &Ruleset) -> bool {
     use PlacementKind::*;
-    matches!(
-        placement_kind,
-        Clear4 | MiniTspin | MiniTspin1 | MiniTspin2 | Tspin | Tspin1 | Tspin2 | Tspin3
-    )
+    match placement_kind {
+        Clear4 | MiniTspin | MiniTspin1 | MiniTspin2 | Tspin | Tspin1 | Tspin2 | Tspin3 => true,
+        MiniSpin { .. } | Spin { .. } => ruleset.special_attack.spin_b2b,
+        None | Clear1 | Clear2 | Clear3 => false,
+    }
 }
 
 impl State {
-    pub fn new_random_state() -> Self {
-        let mut next_pieces: Vec<_> = Piece::iter().collect();
-        let mut rng = thread_rng();
-        next_pieces.shuffle(&mut rng);
-        let mut next_pieces = VecDeque::from(next_pieces);
-
-        State {
+    pub fn new_random_state(randomizer: &mut impl Randomizer, rng: &mut impl Rng) -> Self {
+        let mut state = State {
             board: Board::new(),
-            current_piece: Some(next_pieces.pop_front().unwrap()),
+            current_piece: None,
             hold_piece: None,
-            next_pieces,
-            b2b: false,
+            next_pieces: VecDeque::new(),
+            b2b_chain: 0,
+            bag: 0,
             last_action: None,
+            garbage_queue: VecDeque::new(),
+        };
+
+        state.extend_next_pieces(randomizer, rng);
+        state.current_piece = state.next_pieces.pop_front();
+        state
+    }
+
+    pub fn extend_next_pieces(&mut self, randomizer: &mut impl Randomizer, rng: &mut impl Rng) {
+        for _ in 0..NEXT_PIECES_CHUNK {
+            self.push_next_piece(randomizer.next_piece(rng));
         }
     }
 
-    pub fn extend_next_pieces(&mut self) {
-        let mut new_next_pieces: Vec<_> = Piece::iter().collect();
-        let mut rng = thread_rng();
-        new_next_pieces.shuffle(&mut rng);
-        self.next_pieces.extend(new_next_pieces);
+    pub fn push_next_piece(&mut self, piece: Piece) {
+        if self.bag & piece.bit() == 0 {
+            // a new bag starts (or the randomizer is not a 7-bag)
+            self.bag = ALL_PIECES;
+        }
+        self.bag &= !piece.bit();
+        self.next_pieces.push_back(piece);
     }
 
-    pub fn receive_garbage(&mut self, garbage: u32) {
-        self.board = self.board.receive_garbage(garbage);
+    // the inverse of `push_next_piece`, used to hide pieces beyond the preview
+    pub fn truncate_next_pieces(&mut self, len: usize) {
+        while self.next_pieces.len() > len {
+            let piece = self.next_pieces.pop_back().unwrap();
+            self.bag |= piece.bit();
+            if self.bag == ALL_PIECES {
+                self.bag = 0;
+            }
+        }
+    }
+
+    // pieces that can come after the next pieces
+    // NOTE: only a 7-bag is inferred, and any piece can come with the other randomizers
+    pub fn possible_next_pieces(&self, ruleset: &Ruleset) -> Vec<Piece> {
+        let bag = match ruleset.randomizer {
+            RandomizerKind::SevenBag if self.bag != 0 => self.bag,
+            _ => ALL_PIECES,
+        };
+        Piece::iter()
+            .filter(|piece| bag & piece.bit() != 0)
+            .collect()
+    }
+
+    pub fn receive_garbage(&mut self, garbage: u32, ruleset: &Ruleset, rng: &mut impl Rng) {
+        self.board = self.board.receive_garbage(garbage, ruleset, rng);
+    }
+
+    // garbage sent by an opponent, which arrives after the delay of the rules
+    // sender_lead: how far the clock of the sender is ahead of this game (negative: behind)
+    // NOTE: only a cancel by the last placement changes the hole for `change_on_cancel`
+    pub fn queue_garbage(
+        &mut self,
+        garbage: u32,
+        sender_lead: i32,
+        ruleset: &Ruleset,
+        rng: &mut impl Rng,
+    ) {
+        let delay = (ruleset.garbage.delay as i32 + sender_lead).max(0) as Time;
+        let last_hole = match self.garbage_queue.back() {
+            Some(last) => Some(last.hole),
+            None => self.board.garbage_hole(),
+        };
+        let change_hole = ruleset.garbage.change_on_cancel
+            && self
+                .last_action
+                .as_ref()
+                .is_some_and(|last_action| last_action.garbage_cancelled > 0);
+        self.garbage_queue.extend(Garbage::random(
+            garbage,
+            delay,
+            last_hole,
+            change_hole,
+            ruleset,
+            rng,
+        ));
     }
 
-    pub fn is_dead(&self) -> bool {
+    // the lines of garbage that have not entered the board yet
+    pub fn pending_garbage(&self) -> u32 {
+        self.garbage_queue
+            .iter()
+            .map(|garbage| garbage.amount)
+            .sum()
+    }
+
+    pub fn is_dead(&self, ruleset: &Ruleset) -> bool {
         let initial_movment_state = MovementState::new_from_piece(
             self.current_piece.unwrap(),
             self.hold_piece,
             self.next_pieces.clone(),
+            ruleset.rotation_system,
         );
 
         !self.board.attempt(initial_movment_state.field_piece)
     }
 
-    fn next_state(&self, mut movement_state: MovementState, time: Time) -> State {
-        use PlacementKind::*;
+    // outgoing attack cancels incoming garbage, and the garbage that has arrived
+    // while the piece was placed enters the board if no lines are cleared
+    pub(crate) fn state_after(
+        &self,
+        movement_state: MovementState,
+        new_board: Board,
+        placement_kind: PlacementKind,
+        time: Time,
+        ruleset: &Ruleset,
+    ) -> State {
+        let mut state = self.placed_state(movement_state, new_board, placement_kind, time, ruleset);
+        let last_action = state.last_action.as_mut().unwrap();
 
-        let (new_board, placement_kind) = self.board.place_piece(&movement_state);
+        last_action.garbage_cancelled =
+            cancel_garbage(&mut state.garbage_queue, last_action.garbage_sent);
 
-        if placement_kind == PlacementKind::None {
+        for garbage in state.garbage_queue.iter_mut() {
+            garbage.delay = garbage.delay.saturating_sub(last_action.time);
+        }
+        if placement_kind.cleared_lines() == 0 {
+            state.board = receive_arrived_garbage(&state.board, &mut state.garbage_queue, ruleset);
+        }
+
+        state
+    }
+
+    fn placed_state(
+        &self,
+        mut movement_state: MovementState,
+        new_board: Board,
+        placement_kind: PlacementKind,
+        time: Time,
+        ruleset: &Ruleset,
+    ) -> State {
+        // placements without line clears reset the combo, but keep the b2b chain
+        if placement_kind.cleared_lines() == 0 {
             return State {
                 board: new_board,
                 current_piece: movement_state.next_pieces.pop_front(),
                 hold_piece: movement_state.hold_piece,
                 next_pieces: movement_state.next_pieces,
-                b2b: is_b2b_enabled(placement_kind),
+                b2b_chain: self.b2b_chain,
+                bag: self.bag,
+                garbage_queue: self.garbage_queue.clone(),
                 last_action: Some(LastAction {
                     placement_kind,
                     placed_piece: movement_state.field_piece.piece_state.piece,
+                    field_piece: movement_state.field_piece,
                     b2b: false,
+                    b2b_chain: self.b2b_chain,
                     combo: 0,
                     perfect_clear: false,
                     garbage_sent: 0,
-                    time: time + DEFAULT_ACTION_TIME.place,
+                    garbage_cancelled: 0,
+                    time: time + ruleset.action_time.place,
                     movements_history: movement_state.movements_history,
                 }),
             };
         }
 
+        let (b2b, b2b_chain, surge) = if is_b2b_enabled(placement_kind, ruleset) {
+            (self.b2b_chain > 0, self.b2b_chain + 1, 0)
+        } else {
+            (false, 0, ruleset.special_attack.surge(self.b2b_chain))
+        };
+
         if new_board.is_empty() {
             return State {
                 board: new_board,
                 current_piece: movement_state.next_pieces.pop_front(),
                 hold_piece: movement_state.hold_piece,
                 next_pieces: movement_state.next_pieces,
-                b2b: is_b2b_enabled(placement_kind),
+                b2b_chain,
+                bag: self.bag,
+                garbage_queue: self.garbage_queue.clone(),
                 last_action: Some(LastAction {
                     placement_kind,
                     placed_piece: movement_state.field_piece.piece_state.piece,
-                    b2b: false,
+                    field_piece: movement_state.field_piece,
+                    b2b,
+                    b2b_chain,
                     combo: 1,
                     perfect_clear: true,
-                    garbage_sent: DEFAULT_SPECIAL_ATTACK.perfect_clear,
-                    time: time + DEFAULT_ACTION_TIME.perfect_clear,
+                    garbage_sent: ruleset.special_attack.perfect_clear + surge,
+                    garbage_cancelled: 0,
+                    time: time + ruleset.action_time.perfect_clear,
                     movements_history: movement_state.movements_history,
                 }),
             };
         }
 
-        let base_attack = base_attack(placement_kind);
         let combo = match &self.last_action {
             Some(last_action) => last_action.combo + 1,
             Option::None => 0,
         };
-        let combo_attack = combo_attack(combo);
-        let b2b_attack = if self.b2b && is_b2b_enabled(placement_kind) {
-            DEFAULT_SPECIAL_ATTACK.b2b
-        } else {
-            0
-        };
-        let garbage_sent = base_attack + combo_attack + b2b_attack;
-        let action_time = match placement_kind {
-            Clear1 | MiniTspin1 | Tspin1 => DEFAULT_ACTION_TIME.single,
-            Clear2 | MiniTspin2 | Tspin2 => DEFAULT_ACTION_TIME.double,
-            Clear3 | Tspin3 => DEFAULT_ACTION_TIME.triple,
-            Clear4 => DEFAULT_ACTION_TIME.tetris,
-            _ => DEFAULT_ACTION_TIME.place,
+        let garbage_sent =
+            ruleset.attack(placement_kind, combo, if b2b { self.b2b_chain } else { 0 }) + surge;
+        let action_time = match placement_kind.cleared_lines() {
+            1 => ruleset.action_time.single,
+            2 => ruleset.action_time.double,
+            3 => ruleset.action_time.triple,
+            4 => ruleset.action_time.tetris,
+            _ => ruleset.action_time.place,
         };
 
         State {
@@ -157,74 +299,47 @@ impl State {
             current_piece: movement_state.next_pieces.pop_front(),
             hold_piece: movement_state.hold_piece,
             next_pieces: movement_state.next_pieces,
-            b2b: is_b2b_enabled(placement_kind),
+            b2b_chain,
+            bag: self.bag,
+            garbage_queue: self.garbage_queue.clone(),
             last_action: Some(LastAction {
                 placement_kind,
                 placed_piece: movement_state.field_piece.piece_state.piece,
-                b2b: self.b2b && is_b2b_enabled(placement_kind),
+                field_piece: movement_state.field_piece,
+                b2b,
+                b2b_chain,
                 combo,
                 perfect_clear: false,
                 garbage_sent,
+                garbage_cancelled: 0,
                 time: time + action_time,
                 movements_history: movement_state.movements_history,
             }),
         }
     }
 
-    // dijkstra's algorithm
-    pub fn legal_actions(&self) -> Vec<State> {
-        if self.current_piece.is_none() || self.is_dead() {
-            return vec![];
-        }
-
-        let initial_movment_state = MovementState::new_from_piece(
-            self.current_piece.unwrap(),
+    // the state after the piece is placed where another game says, without finding a path
+    // NOTE: the spin is not detected, since the movements are unknown
+    pub fn place_at(&self, field_piece: FieldPiece, ruleset: &Ruleset) -> Option<State> {
+        let current_piece = self.current_piece?;
+        let mut movement_state = MovementState::new_from_piece(
+            current_piece,
             self.hold_piece,
             self.next_pieces.clone(),
+            ruleset.rotation_system,
         );
-
-        // priority queue
-        let mut queue = BinaryHeap::new();
-        let mut movement_times = HashMap::new();
-
-        queue.push(initial_movment_state.clone());
-        movement_times.insert(initial_movment_state.clone(), 0);
-
-        if let Some(held_movement_state) = initial_movment_state.hold() {
-            if self.board.attempt(held_movement_state.field_piece) {
-                let held_movement_time = held_movement_state.time;
-                queue.push(held_movement_state.clone());
-                movement_times.insert(held_movement_state, held_movement_time);
-            }
-        };
-
-        while let Some(current_movement_state) = queue.pop() {
-            let current_movement_time = current_movement_state.time;
-
-            if let Some(best_time) = movement_times.get(&current_movement_state) {
-                if current_movement_time > *best_time {
-                    continue;
-                }
-            };
-
-            for next_movement_state in self.board.legal_moves(current_movement_state) {
-                let next_time = current_movement_time + next_movement_state.time;
-
-                if let Some(best_time) = movement_times.get(&next_movement_state) {
-                    if next_time >= *best_time {
-                        continue;
-                    }
-                }
-
-                movement_times.insert(next_movement_state.clone(), next_time);
-                queue.push(next_movement_state);
-            }
+        if field_piece.piece_state.piece != current_piece {
+            movement_state = movement_state.hold(ruleset)?;
+        }
+        if movement_state.field_piece.piece_state.piece != field_piece.piece_state.piece
+            || !self.board.attempt(field_piece)
+        {
+            return None;
         }
 
-        movement_times
-            .into_iter()
-            .filter(|(movement_state, _)| movement_state.field_piece.is_locked)
-            .map(|(movement_state, time)| self.next_state(movement_state, time))
-            .collect()
+        movement_state.field_piece = field_piece;
+        let (new_board, placement_kind) = self.board.place_field_piece(field_piece, None, ruleset);
+        let time = movement_state.time;
+        Some(self.state_after(movement_state, new_board, placement_kind, time, ruleset))
     }
 }
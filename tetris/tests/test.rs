@@ -3,6 +3,7 @@ use std::{
     collections::VecDeque,
     hash::{DefaultHasher, Hash, Hasher},
 };
+use tetris::Randomizer;
 
 #[test]
 fn hash_of_movement_state() {
@@ -10,6 +11,7 @@ fn hash_of_movement_state() {
         tetris::Piece::I,
         Some(tetris::Piece::T),
         VecDeque::from(vec![tetris::Piece::L, tetris::Piece::J]),
+        tetris::RotationSystemKind::Srs,
     );
 
     let mut right_movement_state = left_movement_state.clone();
@@ -32,7 +34,7 @@ fn hash_of_movement_state() {
 
     assert_eq!(left_hasher.finish(), right_hasher.finish());
 
-    let right_movement_state = right_movement_state.hold();
+    let right_movement_state = right_movement_state.hold(&tetris::Ruleset::default());
     let mut right_hasher = DefaultHasher::new();
     right_movement_state.hash(&mut right_hasher);
 
@@ -43,17 +45,19 @@ fn hash_of_movement_state() {
 #[test]
 fn random_play() {
     let mut rng = thread_rng();
-    let mut current_state = tetris::State::new_random_state();
+    let mut randomizer = tetris::Bag::new(1);
+    let ruleset = tetris::Ruleset::default();
+    let mut current_state = tetris::State::new_random_state(&mut randomizer, &mut rng);
 
     loop {
         println!("{}", termion::clear::All);
         println!("{}", current_state);
 
-        let legal_actions = current_state.legal_actions();
+        let legal_actions = current_state.legal_actions(&ruleset);
         if let Some(next_state) = legal_actions.choose(&mut rng) {
             current_state = next_state.clone();
             if current_state.next_pieces.len() < 8 {
-                current_state.extend_next_pieces();
+                current_state.extend_next_pieces(&mut randomizer, &mut rng);
             }
         } else {
             break;
@@ -66,24 +70,26 @@ fn random_play() {
 #[test]
 fn receive_garbage() {
     let mut rng = thread_rng();
-    let mut current_state = tetris::State::new_random_state();
+    let mut randomizer = tetris::Bag::new(1);
+    let ruleset = tetris::Ruleset::default();
+    let mut current_state = tetris::State::new_random_state(&mut randomizer, &mut rng);
 
     for _ in 0..3 {
         println!("{}", termion::clear::All);
         println!("{}", current_state);
 
-        let legal_actions = current_state.legal_actions();
+        let legal_actions = current_state.legal_actions(&ruleset);
         if let Some(next_state) = legal_actions.choose(&mut rng) {
             current_state = next_state.clone();
             if current_state.next_pieces.len() < 8 {
-                current_state.extend_next_pieces();
+                current_state.extend_next_pieces(&mut randomizer, &mut rng);
             }
         } else {
             break;
         }
     }
 
-    current_state.receive_garbage(5);
+    current_state.receive_garbage(5, &ruleset, &mut rng);
 
     println!("{}", termion::clear::All);
     println!("{}", current_state);
@@ -93,28 +99,31 @@ fn receive_garbage() {
 #[test]
 fn display_two_states() {
     let mut rng = thread_rng();
-    let mut p1 = tetris::State::new_random_state();
-    let mut p2 = tetris::State::new_random_state();
+    let mut p1_randomizer = tetris::Bag::new(1);
+    let mut p2_randomizer = tetris::Bag::new(1);
+    let ruleset = tetris::Ruleset::default();
+    let mut p1 = tetris::State::new_random_state(&mut p1_randomizer, &mut rng);
+    let mut p2 = tetris::State::new_random_state(&mut p2_randomizer, &mut rng);
 
     loop {
         println!("{}", termion::clear::All);
         println!("{}", tetris::PairState(p1.clone(), p2.clone()));
 
-        let legal_actions = p1.legal_actions();
+        let legal_actions = p1.legal_actions(&ruleset);
         if let Some(next_state) = legal_actions.choose(&mut rng) {
             p1 = next_state.clone();
             if p1.next_pieces.len() < 8 {
-                p1.extend_next_pieces();
+                p1.extend_next_pieces(&mut p1_randomizer, &mut rng);
             }
         } else {
             break;
         }
 
-        let legal_actions = p2.legal_actions();
+        let legal_actions = p2.legal_actions(&ruleset);
         if let Some(next_state) = legal_actions.choose(&mut rng) {
             p2 = next_state.clone();
             if p2.next_pieces.len() < 8 {
-                p2.extend_next_pieces();
+                p2.extend_next_pieces(&mut p2_randomizer, &mut rng);
             }
         } else {
             break;
@@ -123,3 +132,1012 @@ fn display_two_states() {
         std::thread::sleep(std::time::Duration::from_millis(100));
     }
 }
+
+// the t-spin triple below is only reachable with the last kick of the SRS table
+#[test]
+fn tspin_triple_kick() {
+    let state = tetris::State::from_ascii(
+        "
+        current: T
+        ...#......
+        ..........
+        ###.######
+        ##..######
+        ###.######
+        ",
+    )
+    .unwrap();
+
+    assert!(state
+        .legal_actions(&tetris::Ruleset::default())
+        .iter()
+        .any(|next_state| {
+            next_state.last_action.as_ref().unwrap().placement_kind == tetris::PlacementKind::Tspin3
+        }));
+}
+
+#[test]
+fn rotation_systems() {
+    use tetris::RotationSystemKind::*;
+
+    for rotation_system in [Srs, SrsPlus, Ars, Nrs] {
+        let ruleset = tetris::Ruleset {
+            rotation_system,
+            ..Default::default()
+        };
+
+        for piece in [tetris::Piece::T, tetris::Piece::O, tetris::Piece::I] {
+            let state = tetris::State {
+                board: tetris::Board::new(),
+                current_piece: Some(piece),
+                hold_piece: None,
+                next_pieces: VecDeque::new(),
+                b2b_chain: 0,
+                bag: 0,
+                last_action: None,
+                garbage_queue: Default::default(),
+            };
+
+            assert!(!state.legal_actions(&ruleset).is_empty());
+        }
+    }
+}
+
+#[test]
+fn rotate_180_switch() {
+    let state = tetris::State {
+        board: tetris::Board::new(),
+        current_piece: Some(tetris::Piece::T),
+        hold_piece: None,
+        next_pieces: VecDeque::new(),
+        b2b_chain: 0,
+        bag: 0,
+        last_action: None,
+        garbage_queue: Default::default(),
+    };
+
+    let uses_rotate_180 = |ruleset: &tetris::Ruleset| {
+        state.legal_actions(ruleset).iter().any(|next_state| {
+            next_state
+                .last_action
+                .as_ref()
+                .unwrap()
+                .movements_history
+                .contains(&tetris::PieceMovement::Rotate180)
+        })
+    };
+
+    let mut ruleset = tetris::Ruleset {
+        rotation_system: tetris::RotationSystemKind::SrsPlus,
+        ..Default::default()
+    };
+    assert!(!uses_rotate_180(&ruleset));
+
+    ruleset.rotate_180 = true;
+    assert!(uses_rotate_180(&ruleset));
+}
+
+#[test]
+fn jstris_180_has_no_kicks() {
+    use tetris::RotationSystem;
+
+    let jstris = tetris::Ruleset::jstris();
+    assert!(jstris.rotate_180);
+    for piece in [tetris::Piece::T, tetris::Piece::I, tetris::Piece::S] {
+        for from in [
+            tetris::RotationState::North,
+            tetris::RotationState::East,
+            tetris::RotationState::South,
+            tetris::RotationState::West,
+        ] {
+            let kicks = jstris.rotation_system.kicks(piece, from, from.rotate_180());
+            assert_eq!(kicks, &[(0, 0)]);
+        }
+    }
+
+    // unlike tetrio, where a t piece on the floor can be turned upside down
+    let tetrio = tetris::Ruleset::tetrio();
+    let kicks = tetrio.rotation_system.kicks(
+        tetris::Piece::T,
+        tetris::RotationState::North,
+        tetris::RotationState::South,
+    );
+    assert!(kicks.contains(&(0, 1)));
+}
+
+#[test]
+fn attack_presets() {
+    use tetris::PlacementKind::*;
+
+    let guideline = tetris::Ruleset::from_preset("guideline").unwrap();
+    assert_eq!(guideline.attack(Tspin2, 1, 1), 5);
+    assert_eq!(guideline.attack(Clear2, 3, 0), 2);
+
+    let ppt = tetris::Ruleset::from_preset("ppt").unwrap();
+    assert_eq!(ppt.attack(Clear4, 1, 1), 5);
+    assert_eq!(ppt.attack(Tspin3, 1, 1), 7);
+    assert_eq!(ppt.attack(MiniTspin2, 1, 0), 1);
+    assert_eq!(ppt.combo_attack(2), 1);
+    assert_eq!(ppt.combo_attack(11), 5);
+    assert_eq!(ppt.special_attack.perfect_clear, 10);
+    assert!(!ppt.rotate_180);
+
+    let tetris_99 = tetris::Ruleset::from_preset("tetris99").unwrap();
+    assert_eq!(tetris_99.preview, 6);
+    assert_eq!(tetris_99.attack(Tspin2, 3, 1), 6);
+
+    let jstris = tetris::Ruleset::from_preset("jstris").unwrap();
+    assert_eq!(jstris.combo_attack(4), 1);
+
+    let tetrio = tetris::Ruleset::from_preset("tetrio").unwrap();
+    assert_eq!(tetrio.attack(Clear4, 1, 0), 4);
+    assert_eq!(tetrio.attack(Clear4, 5, 0), 8);
+    assert_eq!(tetrio.attack(Tspin2, 1, 8), 7);
+    assert_eq!(tetrio.combo_attack(3), 1);
+}
+
+#[test]
+fn b2b_chain_and_surge() {
+    let ruleset = tetris::Ruleset::tetrio_season2();
+
+    let state_with_well = |depth: usize| {
+        let mut board = tetris::Board::new();
+        for y in 0..depth {
+            board.cells[y] = 0x3ff & !tetris::row_x(9);
+        }
+        // keep the board from being a perfect clear
+        board.cells[depth] = tetris::row_x(0);
+        board.collumn_heights = [0; 10];
+        board.collumn_heights[0] = depth as u32 + 1;
+        for x in 1..9 {
+            board.collumn_heights[x] = depth as u32;
+        }
+
+        tetris::State {
+            board,
+            current_piece: Some(tetris::Piece::I),
+            hold_piece: None,
+            next_pieces: VecDeque::new(),
+            b2b_chain: 5,
+            bag: 0,
+            last_action: None,
+            garbage_queue: Default::default(),
+        }
+    };
+
+    let find_action = |state: tetris::State, placement_kind: tetris::PlacementKind| {
+        state
+            .legal_actions(&ruleset)
+            .into_iter()
+            .find_map(|next_state| {
+                Some(next_state.last_action.unwrap())
+                    .filter(|last_action| last_action.placement_kind == placement_kind)
+            })
+            .unwrap()
+    };
+
+    // a tetris continues the chain
+    let last_action = find_action(state_with_well(4), tetris::PlacementKind::Clear4);
+    assert!(last_action.b2b);
+    assert_eq!(last_action.b2b_chain, 6);
+    assert_eq!(last_action.garbage_sent, 5);
+
+    // a single breaks the chain and releases the surge
+    let last_action = find_action(state_with_well(1), tetris::PlacementKind::Clear1);
+    assert!(!last_action.b2b);
+    assert_eq!(last_action.b2b_chain, 0);
+    assert_eq!(last_action.garbage_sent, 4);
+}
+
+#[test]
+fn all_spin() {
+    // the same shape as the t-spin triple, but with the foot of the J piece
+    let state = tetris::State::from_ascii(
+        "
+        current: J
+        ...#......
+        ..........
+        ###.######
+        ###.######
+        ##..######
+        ",
+    )
+    .unwrap();
+
+    let spin_kinds = |ruleset: &tetris::Ruleset| {
+        state
+            .legal_actions(ruleset)
+            .into_iter()
+            .map(|next_state| next_state.last_action.unwrap().placement_kind)
+            .filter(|placement_kind| {
+                matches!(
+                    placement_kind,
+                    tetris::PlacementKind::MiniSpin { .. } | tetris::PlacementKind::Spin { .. }
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert!(spin_kinds(&tetris::Ruleset::default()).is_empty());
+
+    let ruleset = tetris::Ruleset {
+        spin_rule: tetris::SpinRule::AllMini,
+        ..Default::default()
+    };
+    assert!(
+        spin_kinds(&ruleset).contains(&tetris::PlacementKind::MiniSpin {
+            piece: tetris::Piece::J,
+            lines: 3
+        })
+    );
+
+    // the spin continues the b2b chain unless the rules opt out
+    let b2b_chain_after_spin = |spin_b2b: bool| {
+        let ruleset = tetris::Ruleset {
+            special_attack: tetris::SpecialAttack {
+                spin_b2b,
+                ..Default::default()
+            },
+            ..ruleset.clone()
+        };
+        let mut state = state.clone();
+        state.b2b_chain = 1;
+        state
+            .legal_actions(&ruleset)
+            .into_iter()
+            .find(|next_state| {
+                let placement_kind = next_state.last_action.as_ref().unwrap().placement_kind;
+                matches!(placement_kind, tetris::PlacementKind::MiniSpin { .. })
+            })
+            .unwrap()
+            .b2b_chain
+    };
+    assert_eq!(b2b_chain_after_spin(true), 2);
+    assert_eq!(b2b_chain_after_spin(false), 0);
+}
+
+#[test]
+fn seeded_rng() {
+    let ruleset = tetris::Ruleset::default();
+
+    let play = |seed: u64| {
+        let mut rng = tetris::seeded_rng(seed);
+        let mut randomizer = tetris::Bag::new(1);
+        let mut state = tetris::State::new_random_state(&mut randomizer, &mut rng);
+        state.extend_next_pieces(&mut randomizer, &mut rng);
+        state.receive_garbage(4, &ruleset, &mut rng);
+        state
+    };
+
+    assert_eq!(play(42), play(42));
+    assert!((0..8).any(|seed| play(seed) != play(42)));
+}
+
+#[test]
+fn randomizers() {
+    use tetris::RandomizerKind::*;
+
+    let mut rng = tetris::seeded_rng(0);
+    let draw = |kind: tetris::RandomizerKind, count: usize, rng: &mut tetris::GameRng| {
+        let mut randomizer = kind.build().unwrap();
+        (0..count)
+            .map(|_| randomizer.next_piece(rng))
+            .collect::<Vec<_>>()
+    };
+
+    // every bag contains each piece once (twice for the 14-bag)
+    let pieces = draw(SevenBag, 7, &mut rng);
+    assert!((0..7).all(|i| !pieces[i + 1..].contains(&pieces[i])));
+    let pieces = draw(FourteenBag, 14, &mut rng);
+    assert!(pieces
+        .iter()
+        .all(|&piece| pieces.iter().filter(|&&p| p == piece).count() == 2));
+
+    for kind in [Random, Nes, Tgm] {
+        assert_eq!(draw(kind, 100, &mut rng).len(), 100);
+    }
+
+    use tetris::Piece::*;
+    assert_eq!(
+        draw(Fixed("TIO SZ".to_string()), 7, &mut rng),
+        vec![T, I, O, S, Z, T, I]
+    );
+    use tetris::FixedSequenceError::*;
+    assert_eq!(
+        "TIX".parse::<tetris::FixedSequence>().err(),
+        Some(InvalidPiece('X'))
+    );
+    assert_eq!(" ".parse::<tetris::FixedSequence>().err(), Some(Empty));
+    assert!(Fixed("TIX".to_string()).build().is_err());
+    assert_eq!(
+        Fixed("".to_string()).validate(),
+        Err("the fixed sequence is empty".to_string())
+    );
+}
+
+#[test]
+fn bag_inference() {
+    use tetris::Piece::*;
+
+    let ruleset = tetris::Ruleset::default();
+    let mut randomizer: tetris::FixedSequence = "SZJLTOI IOTLJZS".parse().unwrap();
+    let mut state = tetris::State::new_random_state(&mut randomizer, &mut thread_rng());
+    assert_eq!(state.possible_next_pieces(&ruleset).len(), 7);
+
+    state.push_next_piece(I);
+    state.push_next_piece(O);
+    assert_eq!(state.possible_next_pieces(&ruleset), vec![S, Z, J, L, T]);
+
+    // the bag is not inferred with another randomizer
+    let random_ruleset = tetris::Ruleset {
+        randomizer: tetris::RandomizerKind::Random,
+        ..Default::default()
+    };
+    assert_eq!(state.possible_next_pieces(&random_ruleset).len(), 7);
+
+    // hiding the pieces beyond the preview restores the bag of the last visible piece
+    let full_state = state.clone();
+    state.truncate_next_pieces(2);
+    assert_eq!(state.possible_next_pieces(&ruleset), vec![L, T, O, I]);
+
+    for &piece in full_state.next_pieces.iter().skip(2) {
+        state.push_next_piece(piece);
+    }
+    assert_eq!(state, full_state);
+}
+
+#[test]
+fn fumen() {
+    let pages = tetris::decode_fumen("v115@vhAAgH").unwrap();
+    assert_eq!(
+        pages,
+        vec![tetris::FumenPage::new(tetris::Board::new(), None)]
+    );
+    assert_eq!(tetris::encode_fumen(&pages), "v115@vhAAgH");
+    assert!(tetris::decode_fumen("v110@vhAAgH").is_none());
+
+    // a T piece at the bottom of an empty field
+    let pages = tetris::decode_fumen("v115@vhAVQJ").unwrap();
+    let field_piece = pages[0].piece.unwrap();
+    assert_eq!(field_piece.piece_state.piece, tetris::Piece::T);
+    assert_eq!(
+        field_piece.piece_state.rotation,
+        tetris::RotationState::North
+    );
+    assert_eq!(field_piece.srs_center(), (4, 0));
+    assert_eq!(tetris::encode_fumen(&pages), "v115@vhAVQJ");
+
+    // the pieces written off the center by the fumen editor, in every rotation
+    {
+        use tetris::{Piece::*, RotationState::*};
+        for (fumen, piece, rotation, cells) in [
+            ("v115@vhARGJ", I, North, [(3, 2), (4, 2), (5, 2), (6, 2)]),
+            ("v115@vhAJGJ", I, East, [(4, 0), (4, 1), (4, 2), (4, 3)]),
+            ("v115@vhAhFJ", I, South, [(2, 2), (3, 2), (4, 2), (5, 2)]),
+            ("v115@vhAZBJ", I, West, [(4, 1), (4, 2), (4, 3), (4, 4)]),
+            ("v115@vhATBJ", O, North, [(4, 2), (4, 3), (5, 2), (5, 3)]),
+            ("v115@vhALGJ", O, East, [(4, 1), (4, 2), (5, 1), (5, 2)]),
+            ("v115@vhAjFJ", O, South, [(3, 1), (3, 2), (4, 1), (4, 2)]),
+            ("v115@vhA7AJ", O, West, [(3, 2), (3, 3), (4, 2), (4, 3)]),
+            ("v115@vhAXBJ", S, North, [(3, 2), (4, 2), (4, 3), (5, 3)]),
+            ("v115@vhAvGJ", S, East, [(4, 2), (4, 3), (5, 1), (5, 2)]),
+            ("v115@vhAHGJ", S, South, [(3, 1), (4, 1), (4, 2), (5, 2)]),
+            ("v115@vhAfGJ", S, West, [(3, 2), (3, 3), (4, 1), (4, 2)]),
+            ("v115@vhAUBJ", Z, North, [(3, 3), (4, 2), (4, 3), (5, 2)]),
+            ("v115@vhAMGJ", Z, East, [(4, 1), (4, 2), (5, 2), (5, 3)]),
+            ("v115@vhAEGJ", Z, South, [(3, 2), (4, 1), (4, 2), (5, 1)]),
+            ("v115@vhA8FJ", Z, West, [(3, 1), (3, 2), (4, 2), (4, 3)]),
+        ] {
+            let field_piece = tetris::decode_fumen(fumen).unwrap()[0].piece.unwrap();
+            assert_eq!(field_piece.piece_state.piece, piece, "{}", fumen);
+            assert_eq!(field_piece.piece_state.rotation, rotation, "{}", fumen);
+            let mut decoded_cells = field_piece.cells();
+            decoded_cells.sort();
+            assert_eq!(decoded_cells, cells, "{}", fumen);
+            assert_eq!(
+                tetris::encode_fumen(&tetris::decode_fumen(fumen).unwrap()),
+                fumen
+            );
+        }
+    }
+
+    let mut board = tetris::Board::new();
+    board.cells[0] = 0x3ff & !tetris::row_x(4);
+    board.cells[1] = 0x3ff & !(tetris::row_x(3) | tetris::row_x(4) | tetris::row_x(5));
+    let board = tetris::Board::from_cells(board.cells);
+
+    let rotation_system = tetris::RotationSystemKind::Srs;
+    let mut pages = vec![];
+    for (piece, rotation, comment) in [
+        (tetris::Piece::T, tetris::RotationState::South, "tsd 100%"),
+        (tetris::Piece::I, tetris::RotationState::West, "テトリス"),
+        (tetris::Piece::O, tetris::RotationState::East, "テトリス"),
+    ] {
+        let piece_state = tetris::PieceState { piece, rotation };
+        let field_piece = tetris::FieldPiece::from_srs_center(piece_state, (4, 1), rotation_system);
+        pages.push(tetris::FumenPage {
+            comment: comment.to_string(),
+            lock: false,
+            ..tetris::FumenPage::new(board, Some(field_piece))
+        });
+    }
+
+    let fumen = tetris::encode_fumen(&pages);
+    assert_eq!(tetris::decode_fumen(&fumen).unwrap(), pages);
+
+    // a piece out of the field is not written as a piece at the top left
+    let piece_state = tetris::PieceState {
+        piece: tetris::Piece::T,
+        rotation: tetris::RotationState::North,
+    };
+    let field_piece = tetris::FieldPiece::from_srs_center(piece_state, (4, 30), rotation_system);
+    let page = tetris::FumenPage::new(board, Some(field_piece));
+    let decoded = tetris::decode_fumen(&tetris::encode_fumen(&[page])).unwrap();
+    assert_eq!(decoded[0].board, board);
+    assert_eq!(decoded[0].piece, None);
+
+    // a game is replayed by locking each piece
+    let ruleset = tetris::Ruleset::default();
+    let mut rng = tetris::seeded_rng(0);
+    let mut states = vec![tetris::State::new_random_state(
+        &mut tetris::Bag::new(1),
+        &mut rng,
+    )];
+    for _ in 0..6 {
+        let legal_actions = states.last().unwrap().legal_actions(&ruleset);
+        states.push(legal_actions.choose(&mut rng).unwrap().clone());
+    }
+
+    let pages = tetris::decode_fumen(&tetris::encode_replay(&states)).unwrap();
+    assert_eq!(pages.len(), states.len());
+    for (page, state) in pages.iter().zip(states.iter()) {
+        assert_eq!(page.board, state.board);
+    }
+}
+
+#[test]
+fn ascii() {
+    let ascii = "
+        current: T
+        hold: -
+        next: IOS
+        b2b: 2
+        ....@.....
+        ...@@@....
+        #.....####
+        ##.#######
+    ";
+
+    let state = tetris::State::from_ascii(ascii).unwrap();
+    assert_eq!(state.current_piece, Some(tetris::Piece::T));
+    assert_eq!(
+        state.next_pieces,
+        vec![tetris::Piece::I, tetris::Piece::O, tetris::Piece::S]
+    );
+    assert_eq!(state.b2b_chain, 2);
+    assert_eq!(state.board.collumn_heights, [2, 1, 0, 1, 1, 1, 2, 2, 2, 2]);
+    assert_eq!(tetris::State::from_ascii(&state.to_ascii()), Some(state));
+
+    let field_piece =
+        tetris::FieldPiece::from_ascii(ascii, tetris::Piece::T, tetris::RotationSystemKind::Srs)
+            .unwrap();
+    assert_eq!(
+        field_piece.piece_state.rotation,
+        tetris::RotationState::North
+    );
+    assert_eq!(field_piece.position, (4, 2));
+
+    let board = tetris::Board::from_ascii(
+        "
+        #.....####
+        ##.#######
+        ",
+    )
+    .unwrap();
+    assert_eq!(board.to_ascii(), "#.....####\n##.#######");
+    assert!(tetris::Board::from_ascii("#.....###").is_none());
+}
+
+#[test]
+fn placements() {
+    use std::collections::{HashMap, HashSet};
+
+    let ruleset = tetris::Ruleset {
+        rotation_system: tetris::RotationSystemKind::SrsPlus,
+        rotate_180: true,
+        ..Default::default()
+    };
+    let mut rng = tetris::seeded_rng(5);
+    let _ = 0;
+    let mut randomizer = tetris::Bag::new(1);
+    let mut state = tetris::State::new_random_state(&mut randomizer, &mut rng);
+    state.receive_garbage(4, &ruleset, &mut rng);
+
+    for _ in 0..12 {
+        // the fastest time of each resulting board, and the spins found
+        let mut legal_times = HashMap::new();
+        let mut legal_kinds = HashSet::new();
+        for next_state in state.legal_actions(&ruleset) {
+            let last_action = next_state.last_action.unwrap();
+            let mut cells = last_action.field_piece.cells();
+            cells.sort();
+            let key = (cells, next_state.hold_piece);
+            let time = legal_times.entry(key).or_insert(last_action.time);
+            *time = last_action.time.min(*time);
+            legal_kinds.insert((key, last_action.placement_kind));
+        }
+
+        let mut times = HashMap::new();
+        let mut kinds = HashSet::new();
+        let placements = state.placements(&ruleset);
+        for placement in &placements {
+            let next_state = state.place(placement, &ruleset);
+            let last_action = next_state.last_action.unwrap();
+            let mut cells = last_action.field_piece.cells();
+            cells.sort();
+            let key = (cells, next_state.hold_piece);
+            let time = times.entry(key).or_insert(last_action.time);
+            *time = last_action.time.min(*time);
+            kinds.insert((key, last_action.placement_kind));
+        }
+
+        assert_eq!(times, legal_times);
+        assert!(kinds.is_superset(&legal_kinds));
+
+        // the inputs are searched again, and take the time of the placement
+        for placement in placements.choose_multiple(&mut rng, 5) {
+            let movements = state.movements(placement, &ruleset).unwrap();
+            assert_eq!(movements.last(), Some(&tetris::PieceMovement::HardDrop));
+            assert_eq!(
+                movements.first() == Some(&tetris::PieceMovement::Hold),
+                placement.hold
+            );
+            // the arr is 0, so a das takes the same time over any distance
+            let time: tetris::Time = movements
+                .iter()
+                .map(|&movement| ruleset.action_time.movement_time(movement, 1))
+                .sum();
+            assert_eq!(time, placement.time);
+        }
+
+        let Some(placement) = placements.choose(&mut rng) else {
+            break;
+        };
+        state = state.place(placement, &ruleset);
+        if state.next_pieces.len() < 8 {
+            state.extend_next_pieces(&mut randomizer, &mut rng);
+        }
+    }
+}
+
+#[test]
+fn distinct_placements() {
+    let ruleset = tetris::Ruleset::default();
+    let placement_count = |piece: tetris::Piece| {
+        let state = tetris::State {
+            current_piece: Some(piece),
+            ..tetris::State::from_ascii("").unwrap()
+        };
+        state.placements(&ruleset).len()
+    };
+
+    // all the rotations of the O piece, and the two of each of the I, S and Z pieces, are the same
+    assert_eq!(placement_count(tetris::Piece::O), 9);
+    assert_eq!(placement_count(tetris::Piece::I), 7 + 10);
+    assert_eq!(placement_count(tetris::Piece::S), 8 + 9);
+    assert_eq!(placement_count(tetris::Piece::T), 8 * 2 + 9 * 2);
+
+    // a board is found once for each spin
+    let state = tetris::State::from_ascii(
+        "
+        current: T
+        hold: J
+        next: SZ
+        ..........
+        ##....####
+        #...######
+        ##.#######
+        ",
+    )
+    .unwrap();
+    let mut results = std::collections::HashSet::new();
+    for placement in state.placements(&ruleset) {
+        let next_state = state.place(&placement, &ruleset);
+        let placement_kind = next_state.last_action.unwrap().placement_kind;
+        assert!(results.insert((next_state.board, next_state.hold_piece, placement_kind)));
+    }
+}
+
+#[test]
+fn finesse() {
+    use tetris::PieceMovement::*;
+
+    let ruleset = tetris::Ruleset::default();
+    let state = tetris::State {
+        current_piece: Some(tetris::Piece::T),
+        ..tetris::State::from_ascii("").unwrap()
+    };
+
+    let finesse = |x: i32, rotation: tetris::RotationState| {
+        let placement = state
+            .placements(&ruleset)
+            .into_iter()
+            .find(|placement| {
+                let field_piece = placement.field_piece;
+                field_piece.position.0 == x && field_piece.piece_state.rotation == rotation
+            })
+            .unwrap();
+        state.finesse(&placement, &ruleset).unwrap()
+    };
+
+    assert_eq!(finesse(4, tetris::RotationState::North), vec![HardDrop]);
+    assert_eq!(
+        finesse(3, tetris::RotationState::North),
+        vec![MoveLeft, HardDrop]
+    );
+    assert_eq!(
+        finesse(1, tetris::RotationState::North),
+        vec![DasLeft, HardDrop]
+    );
+    assert_eq!(
+        finesse(7, tetris::RotationState::North),
+        vec![DasRight, MoveLeft, HardDrop]
+    );
+    assert_eq!(finesse(9, tetris::RotationState::West).len(), 3);
+
+    // walking to the wall is charged once, with an instant auto repeat
+    let ruleset = tetris::Ruleset {
+        action_time: tetris::ActionTime {
+            das: 2,
+            arr: 0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let placement = state
+        .placements(&ruleset)
+        .into_iter()
+        .find(|placement| placement.field_piece.position == (1, 0))
+        .unwrap();
+    assert!(placement.time < ruleset.action_time.move_one * 3 + ruleset.action_time.hard_drop);
+}
+
+#[test]
+fn partial_soft_drop() {
+    // the cave on the left can only be entered by stopping the piece above the floor
+    let state = tetris::State::from_ascii(
+        "
+        current: O
+        ##........
+        ..........
+        ..........
+        ##........
+        ##........
+        ##........
+        ",
+    )
+    .unwrap();
+    let ruleset = tetris::Ruleset::default();
+    let cave = [(0, 3), (0, 4), (1, 3), (1, 4)];
+
+    let placement = state
+        .placements(&ruleset)
+        .into_iter()
+        .find(|placement| {
+            let mut cells = placement.field_piece.cells();
+            cells.sort();
+            cells == cave
+        })
+        .unwrap();
+
+    let movements = state.movements(&placement, &ruleset).unwrap();
+    let soft_drops = movements
+        .iter()
+        .filter(|&&movement| movement == tetris::PieceMovement::SoftDrop)
+        .count();
+    assert!(soft_drops > 0 && soft_drops < 20);
+    assert!(state.legal_actions(&ruleset).iter().any(|next_state| {
+        let mut cells = next_state.last_action.as_ref().unwrap().field_piece.cells();
+        cells.sort();
+        cells == cave
+    }));
+}
+
+#[test]
+fn move_reset_limit() {
+    // every move in the tunnel is on the ground
+    let state = tetris::State::from_ascii(
+        "
+        current: O
+        ########..
+        ..........
+        ..........
+        ",
+    )
+    .unwrap();
+
+    let leftmost_in_tunnel = |limit: Option<u32>| {
+        let ruleset = tetris::Ruleset {
+            move_reset: tetris::MoveReset {
+                limit,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let leftmost = |cells: Vec<[(i32, i32); 4]>| {
+            cells
+                .iter()
+                .filter(|cells| cells.iter().all(|&(_, y)| y < 2))
+                .flat_map(|cells| cells.iter().map(|&(x, _)| x))
+                .min()
+        };
+
+        let placements = state.placements(&ruleset);
+        let legal_actions = state.legal_actions(&ruleset);
+        let leftmost_placement =
+            leftmost(placements.iter().map(|p| p.field_piece.cells()).collect());
+        let leftmost_action = leftmost(
+            legal_actions
+                .iter()
+                .map(|next_state| next_state.last_action.as_ref().unwrap().field_piece.cells())
+                .collect(),
+        );
+        assert_eq!(leftmost_placement, leftmost_action);
+        leftmost_placement.unwrap()
+    };
+
+    assert_eq!(leftmost_in_tunnel(None), 0);
+    assert_eq!(leftmost_in_tunnel(Some(15)), 0);
+    assert_eq!(leftmost_in_tunnel(Some(5)), 3);
+}
+
+#[test]
+fn gravity() {
+    // in 20G, the piece falls beside the wall and cannot climb it
+    let state = tetris::State::from_ascii(
+        "
+        current: O
+        ###.......
+        ###.......
+        ###.......
+        ###.......
+        ",
+    )
+    .unwrap();
+
+    let on_the_wall = |gravity: f64| {
+        let ruleset = tetris::Ruleset {
+            gravity,
+            ..Default::default()
+        };
+        let sorted_cells = |cells: Vec<[(i32, i32); 4]>| {
+            let mut cells: Vec<_> = cells
+                .into_iter()
+                .map(|mut cells| {
+                    cells.sort();
+                    cells
+                })
+                .collect();
+            cells.sort();
+            cells.dedup();
+            cells
+        };
+
+        let placements = sorted_cells(
+            state
+                .placements(&ruleset)
+                .iter()
+                .map(|p| p.field_piece.cells())
+                .collect(),
+        );
+        let legal_actions = sorted_cells(
+            state
+                .legal_actions(&ruleset)
+                .iter()
+                .map(|next_state| next_state.last_action.as_ref().unwrap().field_piece.cells())
+                .collect(),
+        );
+        assert_eq!(placements, legal_actions);
+        placements
+            .iter()
+            .any(|cells| cells.iter().all(|&(x, _)| x < 3))
+    };
+
+    assert!(on_the_wall(0.0));
+    assert!(on_the_wall(1.0));
+    assert!(!on_the_wall(20.0));
+}
+
+#[test]
+fn garbage_queue() {
+    let ruleset = tetris::Ruleset {
+        garbage: tetris::GarbageRule {
+            delay: 100,
+            cap: Some(2),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut state = tetris::State::from_ascii(
+        "
+        current: I
+        next: OOOO
+        #########.
+        #########.
+        #########.
+        #########.
+        #.########
+        ",
+    )
+    .unwrap();
+    state.garbage_queue.extend([
+        tetris::Garbage {
+            amount: 3,
+            hole: 0,
+            delay: 0,
+        },
+        tetris::Garbage {
+            amount: 2,
+            hole: 1,
+            delay: 0,
+        },
+    ]);
+    state.queue_garbage(3, 0, &ruleset, &mut tetris::seeded_rng(0));
+    assert_eq!(state.pending_garbage(), 8);
+
+    // the tetris cancels the oldest garbage, and no garbage enters with a line clear
+    let tetris = state
+        .legal_actions(&ruleset)
+        .into_iter()
+        .find(|next_state| {
+            next_state.last_action.as_ref().unwrap().placement_kind == tetris::PlacementKind::Clear4
+        })
+        .unwrap();
+    let last_action = tetris.last_action.as_ref().unwrap();
+    assert_eq!(last_action.garbage_sent, 4);
+    assert_eq!(last_action.garbage_cancelled, 4);
+    assert_eq!(tetris.pending_garbage(), 4);
+    assert_eq!(tetris.board.to_ascii(), "#.########");
+
+    // the arrived garbage enters, and the rest still travels
+    let mut next_state = tetris.legal_actions(&ruleset).swap_remove(0);
+    assert_eq!(next_state.pending_garbage(), 3);
+    assert!(next_state.garbage_queue[0].delay > 0);
+    assert_eq!(next_state.board.cells[0], 0x3ff & !tetris::row_x(1));
+    assert_eq!(
+        next_state.board,
+        tetris::Board::from_cells(next_state.board.cells)
+    );
+
+    // the hole column of garbage on an empty board is empty
+    let board = tetris::Board::new().insert_garbage(2, 3);
+    assert_eq!(board.collumn_heights, [2, 2, 2, 0, 2, 2, 2, 2, 2, 2]);
+
+    // up to the cap per piece
+    for garbage in next_state.garbage_queue.iter_mut() {
+        garbage.delay = 0;
+    }
+    let next_state = next_state.legal_actions(&ruleset).swap_remove(0);
+    assert_eq!(next_state.pending_garbage(), 1);
+
+    // the garbage of a sender ahead in time travels longer, and of one behind shorter
+    let mut state = tetris::State::from_ascii("current: T").unwrap();
+    for sender_lead in [30, -30, -300] {
+        state.queue_garbage(1, sender_lead, &ruleset, &mut tetris::seeded_rng(0));
+    }
+    let delays: Vec<_> = state
+        .garbage_queue
+        .iter()
+        .map(|garbage| garbage.delay)
+        .collect();
+    assert_eq!(delays, vec![130, 70, 0]);
+}
+
+#[test]
+fn garbage_holes() {
+    let mut rng = tetris::seeded_rng(0);
+    let holes_of = |holes: tetris::GarbageHoles, rng: &mut tetris::GameRng| {
+        let ruleset = tetris::Ruleset {
+            garbage: tetris::GarbageRule {
+                holes,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut state = tetris::State::from_ascii("current: T").unwrap();
+        for _ in 0..20 {
+            state.queue_garbage(4, 0, &ruleset, rng);
+        }
+        state.garbage_queue
+    };
+
+    // a single column for all the garbage
+    let clean = holes_of(tetris::GarbageHoles::Clean, &mut rng);
+    assert_eq!(clean.len(), 20);
+    assert!(clean.iter().all(|garbage| garbage.hole == clean[0].hole));
+
+    // a new column every line
+    let cheese = holes_of(tetris::GarbageHoles::Cheese, &mut rng);
+    assert_eq!(cheese.len(), 80);
+    assert!(cheese
+        .iter()
+        .zip(cheese.iter().skip(1))
+        .all(|(above, below)| above.hole != below.hole));
+
+    // a new column every attack
+    let messiness = tetris::GarbageHoles::Messiness {
+        inside: 0.0,
+        between: 1.0,
+    };
+    let attacks = holes_of(messiness, &mut rng);
+    assert_eq!(attacks.len(), 20);
+    assert!(attacks.iter().all(|garbage| garbage.amount == 4));
+    assert!(attacks
+        .iter()
+        .zip(attacks.iter().skip(1))
+        .all(|(above, below)| above.hole != below.hole));
+
+    let random = holes_of(tetris::GarbageHoles::Random, &mut rng);
+    assert!(random.len() > 20 && random.len() < 80);
+
+    // the hole moves after garbage is cancelled
+    let ruleset = tetris::Ruleset {
+        garbage: tetris::GarbageRule {
+            holes: tetris::GarbageHoles::Clean,
+            change_on_cancel: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut state = tetris::State::from_ascii(
+        "
+        current: I
+        next: O
+        #########.
+        #########.
+        #########.
+        #########.
+        ",
+    )
+    .unwrap();
+    // the perfect clear cancels 10 lines of them
+    state.queue_garbage(12, 0, &ruleset, &mut rng);
+    let mut tetris = state
+        .legal_actions(&ruleset)
+        .into_iter()
+        .find(|next_state| next_state.last_action.as_ref().unwrap().perfect_clear)
+        .unwrap();
+    tetris.queue_garbage(2, 0, &ruleset, &mut rng);
+    assert_eq!(tetris.garbage_queue.len(), 2);
+    assert_ne!(tetris.garbage_queue[0].hole, tetris.garbage_queue[1].hole);
+}
+
+#[test]
+fn no_spin_after_a_fall() {
+    // in 20G, the T rotated above the shaft falls into the slot, which is not a spin
+    let state = tetris::State::from_ascii(
+        "
+        current: T
+        ..########
+        ..########
+        #.########
+        ",
+    )
+    .unwrap();
+    let ruleset = tetris::Ruleset {
+        gravity: 20.0,
+        ..Default::default()
+    };
+
+    let slots: Vec<_> = state
+        .legal_actions(&ruleset)
+        .into_iter()
+        .map(|next_state| next_state.last_action.unwrap())
+        .filter(|last_action| last_action.placement_kind.cleared_lines() == 2)
+        .collect();
+    assert!(!slots.is_empty());
+    for last_action in slots {
+        assert_eq!(last_action.placement_kind, tetris::PlacementKind::Clear2);
+        assert_eq!(last_action.field_piece.super_rotation_state, None);
+    }
+}